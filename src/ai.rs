@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+
+use crate::breakout::{BALL_SIZE, BRICK_COUNT, BRICK_GAP, BRICK_SIZE, Ball, Breakout, BrickKind, TICK, TickInput};
+use crate::choice::Choice;
+use crate::powerup::PowerUp;
+use crate::rng::Rng;
+
+// a steel brick counts as this many bricks in the way of a tunnel
+const STEEL_WEIGHT: u32 = 100;
+// how far towards the ends of the paddle the strategies aim, leaving room for error
+const MAX_OFFSET: f32 = 0.8;
+
+/// How well the computer plays.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Skill {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    /// Sees everything straight away and never misjudges it.
+    Perfect,
+}
+
+impl Choice for Skill {
+    const CHOICES: &'static [(Skill, &'static str, &'static str)] = &[
+        (Skill::Easy, "easy", "Easy"),
+        (Skill::Normal, "normal", "Normal"),
+        (Skill::Hard, "hard", "Hard"),
+        (Skill::Perfect, "perfect", "Perfect"),
+    ];
+    const KIND: &'static str = "skill level";
+}
+
+impl Skill {
+    /// Ticks between the ball doing something and the paddle reacting to it.
+    pub fn reaction(&self) -> usize {
+        match self {
+            Skill::Easy => 24,
+            Skill::Normal => 16,
+            Skill::Hard => 8,
+            Skill::Perfect => 0,
+        }
+    }
+
+    /// Largest misjudgement of where the ball will land, in pixels either way.
+    pub fn aim_error(&self) -> f32 {
+        match self {
+            Skill::Easy => 28.0,
+            Skill::Normal => 16.0,
+            Skill::Hard => 8.0,
+            Skill::Perfect => 0.0,
+        }
+    }
+
+    /// Fastest the paddle is moved, in pixels per millisecond.
+    pub fn paddle_speed(&self) -> f32 {
+        match self {
+            Skill::Easy => 0.8,
+            Skill::Normal => 1.2,
+            Skill::Hard => 1.6,
+            Skill::Perfect => f32::INFINITY,
+        }
+    }
+}
+
+/// Where on the paddle the computer tries to take the ball, which sets where it goes next.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Strategy {
+    /// Takes the ball on the middle of the paddle, for the steepest and safest return.
+    Safe,
+    /// Sends the ball up the column with the fewest bricks left, to break through to the top
+    /// and let it work along the back of the wall.
+    #[default]
+    Tunnel,
+    /// Takes the ball somewhere different on the paddle every time.
+    Scatter,
+}
+
+impl Choice for Strategy {
+    const CHOICES: &'static [(Strategy, &'static str, &'static str)] = &[
+        (Strategy::Safe, "safe", "Safe"),
+        (Strategy::Tunnel, "tunnel", "Tunnel"),
+        (Strategy::Scatter, "scatter", "Scatter"),
+    ];
+    const KIND: &'static str = "strategy";
+}
+
+/// Where a ball will meet the paddle's height, and how soon.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Landing {
+    /// The ball's left edge when it gets there.
+    pub x: f32,
+    pub ticks: f32,
+}
+
+/// A computer player. It plays through `TickInput`s like anyone else, and its own randomness
+/// comes from a seed, so its games are as repeatable and replayable as a person's.
+pub struct Autoplay {
+    pub skill: Skill,
+    pub strategy: Strategy,
+    rng: Rng,
+    /// Where the paddle should be, as seen on each of the last `reaction` ticks.
+    seen: VecDeque<(f32, bool)>,
+    /// The misjudgement for the ball on its way down, picked again for each return.
+    error: f32,
+    /// Where on the paddle to take the ball under `Strategy::Scatter`.
+    scatter: f32,
+    descending: bool,
+}
+
+impl Autoplay {
+    pub fn new(skill: Skill, strategy: Strategy, seed: u64) -> Self {
+        Self {
+            skill,
+            strategy,
+            rng: Rng::new(seed),
+            seen: VecDeque::new(),
+            error: 0.0,
+            scatter: 0.0,
+            descending: false,
+        }
+    }
+
+    /// The input for the game's next tick.
+    pub fn input(&mut self, game: &Breakout) -> TickInput {
+        let target = self.target(game);
+        let fire = game.balls.iter().any(|ball| ball.caught.is_some()) || game.effects.is_active(PowerUp::Laser);
+        self.seen.push_back((target, fire));
+        let (target, fire) = if self.seen.len() > self.skill.reaction() {
+            self.seen.pop_front().expect("not empty")
+        } else {
+            // nothing has sunk in yet
+            (game.paddle_pos.x, false)
+        };
+
+        let reach = self.skill.paddle_speed() * TICK * 1000.0;
+        let x = game.paddle_pos.x;
+        TickInput { paddle_x: x + (target - x).clamp(-reach, reach), fire }
+    }
+
+    /// Where the paddle's left edge needs to be for the next ball down, right now.
+    fn target(&mut self, game: &Breakout) -> f32 {
+        let width = game.paddle_width();
+        let next = game.balls.iter()
+            .filter(|ball| ball.caught.is_none())
+            .filter_map(|ball| predict_landing(game, ball))
+            .min_by(|a, b| a.ticks.total_cmp(&b.ticks));
+        let Some(landing) = next else {
+            return game.paddle_pos.x;
+        };
+
+        // pick a fresh error and aim for each ball on its way down
+        let descending = game.balls.iter().any(|ball| ball.caught.is_none() && ball.vel.y > 0.0);
+        if descending && !self.descending {
+            let error = self.skill.aim_error();
+            self.error = (self.rng.next_f32() * 2.0 - 1.0) * error;
+            self.scatter = (self.rng.next_f32() * 2.0 - 1.0) * MAX_OFFSET;
+        }
+        self.descending = descending;
+
+        let land_x = landing.x + self.error;
+        let offset = match self.strategy {
+            Strategy::Safe => 0.0,
+            Strategy::Tunnel => tunnel_offset(game, land_x),
+            Strategy::Scatter => self.scatter,
+        };
+        // the inverse of `Breakout::deflect_off_paddle`: put the paddle's centre `offset`
+        // reaches from the ball's centre
+        let reach = (width + BALL_SIZE.x) / 2.0;
+        land_x + BALL_SIZE.x / 2.0 - offset * reach - width / 2.0
+    }
+}
+
+/// Where `ball` will come down to the paddle, bouncing off the side walls and the ceiling on
+/// the way. Bricks are ignored, so the answer is only right once the ball is clear of them.
+pub fn predict_landing(game: &Breakout, ball: &Ball) -> Option<Landing> {
+    let land_y = game.paddle_pos.y - BALL_SIZE.y;
+    // pixels per tick
+    let vel = ball.vel * TICK * 1000.0;
+    let distance = if vel.y > 0.0 {
+        (land_y - ball.pos.y).max(0.0)
+    } else if vel.y < 0.0 {
+        ball.pos.y.max(0.0) + land_y
+    } else {
+        return None;
+    };
+    let ticks = distance / vel.y.abs();
+
+    // unfold the bounces off the side walls into a straight line, then fold it back
+    let span = game.size.x - BALL_SIZE.x;
+    let x = (ball.pos.x + vel.x * ticks).rem_euclid(2.0 * span);
+    let x = if x > span { 2.0 * span - x } else { x };
+    Some(Landing { x, ticks })
+}
+
+/// Where on the paddle to take a ball landing at `land_x` to send it up the column with the
+/// fewest bricks left, from -1 at the left end to 1 at the right.
+fn tunnel_offset(game: &Breakout, land_x: f32) -> f32 {
+    let pitch = BRICK_SIZE.x + BRICK_GAP;
+    let mut blocking = [0; BRICK_COUNT as usize];
+    for brick in &game.bricks {
+        let column = ((brick.pos.x / pitch).round() as usize).min(blocking.len() - 1);
+        blocking[column] += if brick.kind == BrickKind::Steel { STEEL_WEIGHT } else { brick.hits as u32 };
+    }
+    let Some(top) = game.bricks.iter().map(|brick| brick.pos.y).min_by(f32::total_cmp) else {
+        return 0.0;
+    };
+
+    let ball_centre = land_x + BALL_SIZE.x / 2.0;
+    let column_centre = |column: usize| column as f32 * pitch + BRICK_SIZE.x / 2.0;
+    let column = (0..blocking.len())
+        .min_by(|&a, &b| blocking[a].cmp(&blocking[b]).then_with(|| {
+            (column_centre(a) - ball_centre).abs().total_cmp(&(column_centre(b) - ball_centre).abs())
+        }))
+        .expect("there are columns");
+
+    // the straight line from the paddle to the top of the column, as an angle from vertical
+    let dx = column_centre(column) - ball_centre;
+    let dy = game.paddle_pos.y - top;
+    let angle = dx.abs().atan2(dy).to_degrees();
+    let deflection = game.tuning.deflection;
+    // the angles can be set equal, and then every part of the paddle aims the same
+    let spread = (deflection.max_angle - deflection.min_angle).max(f32::EPSILON);
+    let along = (angle - deflection.min_angle) / spread;
+    along.clamp(0.0, MAX_OFFSET) * dx.signum()
+}
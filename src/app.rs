@@ -0,0 +1,576 @@
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use macroquad::input::{get_last_key_pressed, is_key_pressed, is_mouse_button_pressed, KeyCode, MouseButton, show_mouse};
+use macroquad::math::{Vec2, vec2};
+use macroquad::window::set_fullscreen;
+
+use breakout::ai::{Autoplay, Skill, Strategy};
+use breakout::audio::Audio;
+use breakout::breakout::{Breakout, GAME_HEIGHT, GAME_WIDTH, GameState, Input, MAX_PLAYERS, PaddleInput, Tuning};
+use breakout::choice::Choice;
+use breakout::highscore::{Entry, HighScoreError, HighScores};
+use breakout::level::Level;
+use breakout::replay::Replay;
+use breakout::rules::Ruleset;
+use breakout::save::SavedGame;
+
+use crate::config::Config;
+use crate::controls::{Action, Bindings, Controls, Nav};
+use crate::frontend::Frontend;
+use crate::menu::{self, Item, MenuEvent, Pointer};
+use crate::scene::{Scene, SceneStack};
+use crate::screen::Screen;
+
+const VOLUME_STEP: f32 = 0.1;
+// how the computer plays the demo behind the title screen
+const ATTRACT_SKILL: Skill = Skill::Hard;
+const ATTRACT_STRATEGY: Strategy = Strategy::Tunnel;
+
+/// The whole game: the simulation, the scenes stacked over it and everything they share.
+pub struct App {
+    config: Config,
+    screen: Screen,
+    audio: Audio,
+    controls: Controls,
+    frontend: Frontend,
+    high_scores: HighScores,
+    scores_path: Option<PathBuf>,
+    level: Level,
+    game: Breakout,
+    /// The computer player, when it is the one playing.
+    autoplay: Option<Autoplay>,
+    /// The computer taking player 2's turns in a game for two against it.
+    opponent: Option<Autoplay>,
+    /// Whether the game is the demo behind the title screen rather than one being played.
+    attract: bool,
+    scenes: SceneStack,
+    /// Whether this game's score can go on the table. Scores from games for two, custom
+    /// levels, easier settings, a known seed or the computer can't.
+    ranked: bool,
+    /// A game left from the pause menu, offered to continue from the title screen.
+    saved: Option<SavedGame>,
+    save_path: Option<PathBuf>,
+    /// Which table the high-score screen shows.
+    scores_ruleset: Ruleset,
+    /// The action waiting for a new key in the controls menu.
+    rebinding: Option<Action>,
+    last_mouse: Vec2,
+}
+
+impl App {
+    pub fn new(config: Config, level: Level, screen: Screen, mut audio: Audio, mut frontend: Frontend) -> Self {
+        audio.set_volume(config.volume);
+        audio.muted = config.muted;
+        let scores_path = HighScores::default_path();
+        let high_scores = match &scores_path {
+            Some(path) => load_high_scores(path, &mut frontend),
+            None => HighScores::default(),
+        };
+        let save_path = SavedGame::default_path();
+        let saved = match save_path.as_ref().map(SavedGame::load) {
+            Some(Ok(saved)) => saved,
+            Some(Err(err)) => {
+                eprintln!("ignoring saved game: {}", err);
+                None
+            }
+            None => None,
+        };
+        let game = new_game(config.ruleset, &level, &config);
+        let mut app = Self {
+            controls: Controls::new(config.bindings.clone()),
+            ranked: ranked(&config),
+            saved,
+            save_path,
+            scores_ruleset: config.ruleset,
+            config,
+            screen,
+            audio,
+            frontend,
+            high_scores,
+            scores_path,
+            level,
+            game,
+            autoplay: None,
+            opponent: None,
+            attract: false,
+            scenes: SceneStack::new(Scene::Title),
+            rebinding: None,
+            last_mouse: Vec2::ZERO,
+        };
+        app.start_attract();
+        app
+    }
+
+    pub fn update(&mut self) {
+        let top = self.scenes.top();
+        let input = self.controls.input(self.screen.scale());
+        if self.game.game_state != GameState::Paused {
+            // the computer plays the whole game, or player 2's turns in a game against it
+            let computer = match &mut self.autoplay {
+                Some(autoplay) => Some(autoplay),
+                None if top == Scene::Game && self.game.player == 1 => self.opponent.as_mut(),
+                None => None,
+            };
+            match computer {
+                Some(autoplay) => {
+                    for _ in 0..self.game.advance(input.dt) {
+                        let input = autoplay.input(&self.game);
+                        self.game.step(input);
+                    }
+                }
+                // a finished game keeps running behind the results, untouched
+                None if top != Scene::Game => self.game.update(&Input { paddle: PaddleInput::Delta(0.0), fire: false, dt: input.dt }),
+                None => self.game.update(&input),
+            }
+        }
+        if self.attract {
+            // the demo plays silently, and starts over once it ends
+            self.game.sounds.clear();
+            if self.game.game_state != GameState::Playing {
+                self.start_attract();
+            }
+        } else {
+            self.audio.play_all(self.game.sounds.drain(..));
+        }
+
+        let mouse = self.screen.mouse_position();
+        let pointer = Pointer {
+            pos: mouse,
+            moved: mouse != self.last_mouse,
+            clicked: is_mouse_button_pressed(MouseButton::Left),
+        };
+        self.last_mouse = mouse;
+        let nav = self.controls.nav();
+
+        match top {
+            Scene::Game => self.update_game(),
+            Scene::NameEntry => self.update_name_entry(),
+            Scene::Results => match nav {
+                Some(Nav::Select) => self.start_game(self.game.ruleset),
+                Some(Nav::Back) => self.quit_to_title(),
+                _ if pointer.clicked => self.start_game(self.game.ruleset),
+                _ => {}
+            },
+            Scene::HighScores => match nav {
+                Some(Nav::Left) => self.scores_ruleset = self.scores_ruleset.cycle(-1),
+                Some(Nav::Right) => self.scores_ruleset = self.scores_ruleset.cycle(1),
+                Some(Nav::Select | Nav::Back) => self.scenes.pop(),
+                _ if pointer.clicked => self.scenes.pop(),
+                _ => {}
+            },
+            Scene::Credits => {
+                if matches!(nav, Some(Nav::Select | Nav::Back)) || pointer.clicked {
+                    self.scenes.pop();
+                }
+            }
+            Scene::ControlsOptions if self.rebinding.is_some() => self.update_rebinding(pointer.clicked),
+            Scene::Paused if self.controls.pressed(Action::Pause) => self.resume(),
+            scene => {
+                let count = self.menu_items(scene).len();
+                if let Some(event) = menu::navigate(&mut self.scenes.top_layer_mut().selected, count, nav, &pointer) {
+                    self.menu_event(scene, event);
+                }
+            }
+        }
+
+        // typing initials or a new key shouldn't touch the sound
+        if self.scenes.top() != Scene::NameEntry && self.rebinding.is_none() {
+            handle_audio_keys(&mut self.audio, &mut self.frontend, &self.controls);
+        }
+        show_mouse(self.scenes.top() != Scene::Game);
+    }
+
+    pub fn draw(&self) {
+        self.screen.begin();
+        self.frontend.draw(&self.game);
+        let layer = self.scenes.top_layer();
+        match layer.scene {
+            Scene::Game => {}
+            Scene::NameEntry => {
+                if let Some(entry) = &self.frontend.name_entry {
+                    self.frontend.draw_name_entry(&self.game, entry);
+                }
+            }
+            Scene::Results if self.game.waiting.is_some() => {
+                let title = match self.game.winner() {
+                    Some(player) => format!("Player {} wins!", player + 1),
+                    None => "It's a draw!".to_owned(),
+                };
+                let hint = "Enter or click to play again, Escape for the menu";
+                self.frontend.draw_two_player_results(&self.game, &title, hint);
+            }
+            Scene::Results => {
+                let title = if self.game.game_state == GameState::Win { "You win!" } else { "Game over!" };
+                let ruleset = self.game.ruleset;
+                let hint = "Enter or click to play again, Escape for the menu";
+                let table = self.high_scores.table(ruleset);
+                self.frontend.draw_high_scores(&self.game, title, ruleset, table, self.frontend.last_place, hint);
+            }
+            Scene::HighScores => {
+                let ruleset = self.scores_ruleset;
+                let hint = "Left and right to change mode, Escape to go back";
+                let table = self.high_scores.table(ruleset);
+                self.frontend.draw_high_scores(&self.game, "High scores", ruleset, table, None, hint);
+            }
+            Scene::Credits => self.frontend.draw_credits(&self.game),
+            scene => self.frontend.draw_menu(menu_title(scene), &self.menu_items(scene), layer.selected),
+        }
+        self.screen.end();
+    }
+
+    fn update_game(&mut self) {
+        if matches!(self.game.game_state, GameState::GameOver | GameState::Win) {
+            self.game_ended();
+        } else if self.controls.pressed(Action::Pause) {
+            self.game.game_state = GameState::Paused;
+            self.scenes.push(Scene::Paused);
+        } else if self.controls.pressed(Action::Restart) {
+            self.start_game(self.game.ruleset);
+        }
+    }
+
+    fn game_ended(&mut self) {
+        save_replay(&self.game);
+        self.frontend.last_place = None;
+        if self.ranked && self.high_scores.qualifies(self.game.ruleset, self.game.score) {
+            self.frontend.name_entry = Some(Default::default());
+            self.scenes.push(Scene::NameEntry);
+        } else {
+            self.scenes.push(Scene::Results);
+        }
+    }
+
+    fn update_name_entry(&mut self) {
+        let Some(initials) = self.frontend.poll_name_entry() else {
+            return;
+        };
+        let entry = Entry { initials, score: self.game.score };
+        self.frontend.last_place = self.high_scores.insert(self.game.ruleset, entry);
+        if let Some(path) = &self.scores_path {
+            if let Err(err) = self.high_scores.save(path) {
+                eprintln!("could not save high scores: {}", err);
+            }
+        }
+        self.scenes.replace(Scene::Results);
+    }
+
+    /// Binds the next key or gamepad button pressed to the action being rebound, in place of
+    /// the keys or the buttons it had. Escape or a click cancels.
+    fn update_rebinding(&mut self, clicked: bool) {
+        let Some(action) = self.rebinding else {
+            return;
+        };
+        if clicked {
+            self.rebinding = None;
+            return;
+        }
+        if let Some(&button) = self.controls.buttons_pressed().first() {
+            self.controls.bindings.bind_buttons(action, &[button]);
+            self.rebinding = None;
+            return;
+        }
+        match get_last_key_pressed() {
+            Some(KeyCode::Escape) => self.rebinding = None,
+            Some(key) => {
+                self.controls.bindings.bind_keys(action, &[key]);
+                self.rebinding = None;
+            }
+            None => {}
+        }
+    }
+
+    fn menu_items(&self, scene: Scene) -> Vec<Item> {
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match scene {
+            Scene::Title => {
+                let continue_item = self.saved.as_ref().map(|_| Item::new("Continue"));
+                continue_item.into_iter().chain(["Play", "Options", "High scores", "Credits", "Quit"].map(Item::new)).collect()
+            }
+            Scene::ModeSelect => {
+                let mut items: Vec<Item> = Ruleset::all().map(|ruleset| Item::new(ruleset.name())).collect();
+                items.push(Item::setting("Players", self.config.tuning.players.to_string()));
+                if self.config.tuning.players > 1 {
+                    items.push(Item::setting("Player 2", self.config.opponent.map_or("Person", |skill| skill.name())));
+                }
+                items.push(Item::new("Back"));
+                items
+            }
+            Scene::Options => ["Controls", "Audio", "Video", "Back"].map(Item::new).into(),
+            Scene::ControlsOptions => {
+                let mut items: Vec<Item> = Action::ALL
+                    .iter()
+                    .map(|&action| {
+                        let keys = if self.rebinding == Some(action) {
+                            "press a key or button".to_owned()
+                        } else {
+                            let bindings = &self.controls.bindings;
+                            let keys: Vec<String> = bindings.keys(action).map(|key| format!("{:?}", key)).collect();
+                            let buttons: Vec<String> = bindings.buttons(action).map(|button| format!("{:?}", button)).collect();
+                            if buttons.is_empty() {
+                                keys.join(", ")
+                            } else {
+                                format!("{} / {}", keys.join(", "), buttons.join(", "))
+                            }
+                        };
+                        Item::setting(action.label(), keys)
+                    })
+                    .collect();
+                items.extend([Item::new("Reset to defaults"), Item::new("Back")]);
+                items
+            }
+            Scene::AudioOptions => vec![
+                Item::setting("Volume", format!("{}%", (self.audio.volume * 100.0).round())),
+                Item::setting("Sound", on_off(!self.audio.muted)),
+                Item::new("Back"),
+            ],
+            Scene::VideoOptions => vec![
+                Item::setting("Fullscreen", on_off(self.config.fullscreen)),
+                Item::setting("Integer scaling", on_off(self.config.integer_scaling)),
+                Item::new("Back"),
+            ],
+            Scene::Paused => ["Resume", "Restart", "Options", "Save and quit"].map(Item::new).into(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn menu_event(&mut self, scene: Scene, mut event: MenuEvent) {
+        // a saved game puts Continue above the title screen's usual items
+        if scene == Scene::Title && self.saved.is_some() {
+            match event {
+                MenuEvent::Select(0) => return self.continue_game(),
+                MenuEvent::Select(i) => event = MenuEvent::Select(i - 1),
+                _ => {}
+            }
+        }
+        // the last item of every menu but the title's goes back
+        let last = self.menu_items(scene).len() - 1;
+        let event = match event {
+            MenuEvent::Select(i) if i == last && scene != Scene::Title => MenuEvent::Back,
+            event => event,
+        };
+        match (scene, event) {
+            (Scene::Title, MenuEvent::Select(0)) => self.scenes.push(Scene::ModeSelect),
+            (Scene::Title, MenuEvent::Select(1)) => self.scenes.push(Scene::Options),
+            (Scene::Title, MenuEvent::Select(2)) => {
+                self.scores_ruleset = self.game.ruleset;
+                self.scenes.push(Scene::HighScores);
+            }
+            (Scene::Title, MenuEvent::Select(3)) => self.scenes.push(Scene::Credits),
+            (Scene::Title, MenuEvent::Select(4)) => exit(0),
+            (Scene::Title, _) => {}
+
+            (Scene::ModeSelect, MenuEvent::Select(i)) if i < Ruleset::CHOICES.len() => self.start_game(Ruleset::CHOICES[i].0),
+            (Scene::ModeSelect, MenuEvent::Select(i)) if i == Ruleset::CHOICES.len() => {
+                self.config.tuning.players = self.config.tuning.players % MAX_PLAYERS + 1;
+            }
+            (Scene::ModeSelect, MenuEvent::Adjust(i, step)) if i == Ruleset::CHOICES.len() => {
+                let players = self.config.tuning.players as i8 + step;
+                self.config.tuning.players = players.clamp(1, MAX_PLAYERS as i8) as u8;
+            }
+            (Scene::ModeSelect, MenuEvent::Select(_)) => self.config.opponent = cycle_opponent(self.config.opponent, 1),
+            (Scene::ModeSelect, MenuEvent::Adjust(i, step)) if i == Ruleset::CHOICES.len() + 1 => {
+                self.config.opponent = cycle_opponent(self.config.opponent, step as isize);
+            }
+
+            (Scene::Options, MenuEvent::Select(i)) => {
+                self.scenes.push([Scene::ControlsOptions, Scene::AudioOptions, Scene::VideoOptions][i]);
+            }
+            (Scene::Options, MenuEvent::Back) => {
+                self.save_options();
+                self.scenes.pop();
+            }
+
+            (Scene::ControlsOptions, MenuEvent::Select(i)) if i < Action::ALL.len() => self.rebinding = Some(Action::ALL[i]),
+            (Scene::ControlsOptions, MenuEvent::Select(_)) => self.controls.bindings = Bindings::default(),
+
+            (Scene::AudioOptions, MenuEvent::Adjust(0, step)) => {
+                self.audio.set_volume(self.audio.volume + step as f32 * VOLUME_STEP);
+            }
+            (Scene::AudioOptions, MenuEvent::Select(0)) => {
+                // clicking steps up, wrapping round to silent
+                let volume = if self.audio.volume >= 1.0 { 0.0 } else { self.audio.volume + VOLUME_STEP };
+                self.audio.set_volume(volume);
+            }
+            (Scene::AudioOptions, MenuEvent::Select(1) | MenuEvent::Adjust(1, _)) => self.audio.muted = !self.audio.muted,
+
+            (Scene::VideoOptions, MenuEvent::Select(0) | MenuEvent::Adjust(0, _)) => {
+                self.config.fullscreen = !self.config.fullscreen;
+                set_fullscreen(self.config.fullscreen);
+            }
+            (Scene::VideoOptions, MenuEvent::Select(1) | MenuEvent::Adjust(1, _)) => {
+                self.config.integer_scaling = !self.config.integer_scaling;
+                self.screen.set_integer_scaling(self.config.integer_scaling);
+            }
+
+            (Scene::Paused, MenuEvent::Select(0) | MenuEvent::Back) => self.resume(),
+            (Scene::Paused, MenuEvent::Select(1)) => self.start_game(self.game.ruleset),
+            (Scene::Paused, MenuEvent::Select(2)) => self.scenes.push(Scene::Options),
+            (Scene::Paused, MenuEvent::Select(3)) => self.save_and_quit(),
+
+            (_, MenuEvent::Back) => self.scenes.pop(),
+            _ => {}
+        }
+    }
+
+    /// Starts playing straight away, with the title screen to return to.
+    fn start_game(&mut self, ruleset: Ruleset) {
+        self.game = new_game(ruleset, &self.level, &self.config);
+        self.game.start();
+        self.autoplay = self.config.autoplay.map(|skill| Autoplay::new(skill, self.config.strategy, self.game.seed));
+        self.opponent = self.new_opponent();
+        self.attract = false;
+        self.ranked = ranked(&self.config);
+        self.scenes.reset(Scene::Title);
+        self.scenes.push(Scene::Game);
+    }
+
+    /// Picks the saved game up where it was left, paused. A save is only continued once.
+    fn continue_game(&mut self) {
+        let Some(saved) = self.saved.take() else {
+            return;
+        };
+        if let Some(path) = &self.save_path {
+            if let Err(err) = SavedGame::remove(path) {
+                eprintln!("could not remove saved game: {}", err);
+            }
+        }
+        self.game = saved.game;
+        self.autoplay = None;
+        self.opponent = self.new_opponent();
+        self.attract = false;
+        self.ranked = saved.ranked;
+        self.scenes.reset(Scene::Title);
+        self.scenes.push(Scene::Game);
+        self.scenes.push(Scene::Paused);
+    }
+
+    /// Keeps the paused game to continue on a later launch, then goes back to the title screen.
+    fn save_and_quit(&mut self) {
+        let saved = SavedGame::from_game(&self.game, self.ranked);
+        if let Some(path) = &self.save_path {
+            if let Err(err) = saved.save(path) {
+                eprintln!("could not save the game: {}", err);
+            }
+        }
+        self.saved = Some(saved);
+        self.quit_to_title();
+    }
+
+    fn resume(&mut self) {
+        self.game.game_state = GameState::Playing;
+        self.scenes.pop();
+    }
+
+    /// Abandons the game for the title screen, with a fresh attract mode behind it.
+    fn quit_to_title(&mut self) {
+        self.start_attract();
+        self.scenes.reset(Scene::Title);
+    }
+
+    /// Has the computer play a demo game behind the menus.
+    fn start_attract(&mut self) {
+        self.game = new_game(self.game.ruleset, &self.level, &self.config);
+        self.game.start();
+        self.autoplay = Some(Autoplay::new(ATTRACT_SKILL, ATTRACT_STRATEGY, self.game.seed));
+        self.opponent = None;
+        self.attract = true;
+    }
+
+    /// The computer player for player 2's turns, if the game is for two and one was asked for.
+    fn new_opponent(&self) -> Option<Autoplay> {
+        let skill = self.config.opponent.filter(|_| self.game.waiting.is_some())?;
+        Some(Autoplay::new(skill, self.config.strategy, self.game.seed))
+    }
+
+    fn save_options(&mut self) {
+        self.config.bindings = self.controls.bindings.clone();
+        self.config.volume = self.audio.volume;
+        self.config.muted = self.audio.muted;
+        if let Err(err) = self.config.save_options() {
+            eprintln!("could not save options: {}", err);
+        }
+    }
+}
+
+/// Mute with its action, and `-` and `=` for the master volume.
+pub fn handle_audio_keys(audio: &mut Audio, frontend: &mut Frontend, controls: &Controls) {
+    if controls.pressed(Action::Mute) {
+        audio.muted = !audio.muted;
+        frontend.show_notice(if audio.muted { "Sound off".into() } else { "Sound on".into() });
+    }
+    let step = is_key_pressed(KeyCode::Equal) as i8 - is_key_pressed(KeyCode::Minus) as i8;
+    if step != 0 {
+        audio.set_volume(audio.volume + step as f32 * VOLUME_STEP);
+        audio.muted = false;
+        frontend.show_notice(format!("Volume {}%", (audio.volume * 100.0).round()));
+    }
+}
+
+/// The high-score table at `path`. One that can't be read starts over empty, and a damaged file
+/// is moved aside first so that saving the new table doesn't lose the scores in it.
+fn load_high_scores(path: &Path, frontend: &mut Frontend) -> HighScores {
+    let err = match HighScores::load(path) {
+        Ok(scores) => return scores,
+        Err(err) => err,
+    };
+    eprintln!("ignoring high scores: {}", err);
+    if !matches!(err, HighScoreError::Io(_)) {
+        match HighScores::set_aside(path) {
+            Ok(aside) => eprintln!("moved the old high scores to {}", aside.display()),
+            Err(err) => eprintln!("could not move the old high scores aside: {}", err),
+        }
+    }
+    frontend.show_notice("Could not read the high scores".into());
+    HighScores::default()
+}
+
+/// Scores from games for two, custom levels, easier settings, a known seed or the computer
+/// don't go on the table, which is only for the built-in wall.
+fn ranked(config: &Config) -> bool {
+    config.tuning == Tuning::default() && config.level.is_none() && config.seed.is_none() && config.autoplay.is_none()
+}
+
+fn menu_title(scene: Scene) -> &'static str {
+    match scene {
+        Scene::Title => "Breakout!",
+        Scene::ModeSelect => "Choose a mode",
+        Scene::Options => "Options",
+        Scene::ControlsOptions => "Controls",
+        Scene::AudioOptions => "Audio",
+        Scene::VideoOptions => "Video",
+        Scene::Paused => "Game paused",
+        _ => "",
+    }
+}
+
+/// The next choice for player 2, `step` along a person and then each skill, wrapping around.
+fn cycle_opponent(opponent: Option<Skill>, step: isize) -> Option<Skill> {
+    let len = Skill::CHOICES.len() as isize + 1;
+    let i = opponent.map_or(0, |skill| skill.index() as isize + 1);
+    match (i + step).rem_euclid(len) {
+        0 => None,
+        i => Some(Skill::CHOICES[i as usize - 1].0),
+    }
+}
+
+pub fn new_game(ruleset: Ruleset, level: &Level, config: &Config) -> Breakout {
+    let seed = config.seed.unwrap_or_else(random_seed);
+    Breakout::new(vec2(GAME_WIDTH, GAME_HEIGHT), ruleset, level.clone(), seed).with_tuning(config.tuning)
+}
+
+fn random_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+}
+
+/// Keeps a finished game in the replays directory, named by when it ended.
+fn save_replay(game: &Breakout) {
+    let Some(dir) = Replay::default_dir() else {
+        return;
+    };
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    if let Err(err) = Replay::from_game(game).save_new(&dir, &format!("{}-{}", time, game.ruleset.id())) {
+        eprintln!("could not save replay: {}", err);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::level::MAX_ROWS;
+use crate::synth::{self, Envelope, Voice, Waveform};
+
+pub const SAMPLE_RATE: u32 = 44100;
+/// How far apart the brick tones are, in semitones per row.
+const ROW_STEP: f32 = 2.0;
+
+/// Something the player should hear. The simulation queues these as it plays and the frontend
+/// hands them to `Audio`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sound {
+    Paddle,
+    Wall,
+    /// A brick hit, pitched higher the further the row is from the paddle.
+    Brick(u8),
+    LifeLost,
+    Win,
+}
+
+impl Sound {
+    /// Every distinct sound, for loading them all up front.
+    pub fn all() -> impl Iterator<Item = Sound> {
+        let bricks = (0..MAX_ROWS as u8).map(Sound::Brick);
+        [Sound::Paddle, Sound::Wall].into_iter().chain(bricks).chain([Sound::LifeLost, Sound::Win])
+    }
+
+    /// A name for the sound's file when exported.
+    pub fn name(self) -> String {
+        match self {
+            Sound::Paddle => "paddle".into(),
+            Sound::Wall => "wall".into(),
+            Sound::Brick(row) => format!("brick-{}", row),
+            Sound::LifeLost => "life-lost".into(),
+            Sound::Win => "win".into(),
+        }
+    }
+
+    /// The notes that make up the sound.
+    pub fn voices(self) -> Vec<Voice> {
+        let square = Waveform::Square { duty: 0.5 };
+        match self {
+            Sound::Paddle => vec![Voice::new(square, 440.0, 0.03, Envelope::pluck(0.04))],
+            Sound::Wall => vec![Voice::new(Waveform::Triangle, 220.0, 0.02, Envelope::pluck(0.03)).volume(0.8)],
+            Sound::Brick(row) => {
+                // `libm` rather than std, so that the samples come out the same on every platform
+                let freq = 330.0 * libm::powf(2.0, row as f32 * ROW_STEP / 12.0);
+                vec![Voice::new(Waveform::Square { duty: 0.25 }, freq, 0.03, Envelope::pluck(0.05))]
+            }
+            Sound::LifeLost => vec![
+                Voice::new(square, 220.0, 0.3, Envelope::pluck(0.3)).slide_to(55.0),
+                Voice::new(Waveform::Noise, 2000.0, 0.05, Envelope::pluck(0.25)).volume(0.3),
+            ],
+            Sound::Win => [523.25, 659.25, 783.99, 1046.5]
+                .into_iter()
+                .enumerate()
+                .map(|(i, freq)| {
+                    let envelope = Envelope { attack: 0.005, decay: 0.05, sustain: 0.6, release: 0.1 };
+                    Voice::new(Waveform::Triangle, freq, 0.1, envelope).volume(0.7).at(i as f32 * 0.12)
+                })
+                .collect(),
+        }
+    }
+
+    /// The sound as mono samples in `-1.0..=1.0` at `SAMPLE_RATE`.
+    pub fn samples(self) -> Vec<f32> {
+        synth::render(&self.voices(), SAMPLE_RATE)
+    }
+}
+
+/// Where sounds end up.
+pub trait Backend {
+    fn play(&mut self, sound: Sound, volume: f32);
+}
+
+/// Plays nothing, for headless runs and machines without an audio device.
+pub struct NullBackend;
+
+impl Backend for NullBackend {
+    fn play(&mut self, _sound: Sound, _volume: f32) {}
+}
+
+/// Plays sounds through macroquad. Only available with the `audio` feature, since it needs a
+/// working audio device as soon as the window opens.
+#[cfg(feature = "audio")]
+pub struct MacroquadBackend {
+    sounds: Vec<(Sound, macroquad::audio::Sound)>,
+}
+
+#[cfg(feature = "audio")]
+impl MacroquadBackend {
+    /// Renders every sound up front. Any that fail to load are left silent.
+    pub async fn load() -> Self {
+        let mut sounds = Vec::new();
+        for sound in Sound::all() {
+            match macroquad::audio::load_sound_from_bytes(&synth::wav(&sound.samples(), SAMPLE_RATE)).await {
+                Ok(loaded) => sounds.push((sound, loaded)),
+                Err(err) => eprintln!("could not load {:?} sound: {}", sound, err),
+            }
+        }
+        Self { sounds }
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Backend for MacroquadBackend {
+    fn play(&mut self, sound: Sound, volume: f32) {
+        if let Some((_, loaded)) = self.sounds.iter().find(|(s, _)| *s == sound) {
+            let params = macroquad::audio::PlaySoundParams { looped: false, volume };
+            macroquad::audio::play_sound(loaded, params);
+        }
+    }
+}
+
+/// The game's mixer: a backend plus the master volume and mute settings.
+pub struct Audio {
+    backend: Box<dyn Backend>,
+    /// Master volume from 0.0 to 1.0.
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Audio {
+    pub fn new(backend: impl Backend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            volume: 1.0,
+            muted: false,
+        }
+    }
+
+    /// Audio that goes nowhere.
+    pub fn null() -> Self {
+        Self::new(NullBackend)
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+    }
+
+    pub fn play(&mut self, sound: Sound) {
+        if !self.muted && self.volume > 0.0 {
+            self.backend.play(sound, self.volume);
+        }
+    }
+
+    /// Plays a batch of sounds, each at most once so that simultaneous hits don't stack up.
+    pub fn play_all(&mut self, sounds: impl IntoIterator<Item = Sound>) {
+        let mut played = Vec::new();
+        for sound in sounds {
+            if !played.contains(&sound) {
+                played.push(sound);
+                self.play(sound);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::breakout::Fnv;
+
+    /// 64-bit FNV-1a of each sound's exported `.wav`. A change to the synth or to a sound's notes
+    /// shows up here, so update these after listening to the new files from `export-sounds`.
+    const GOLDEN: [(&str, u64); 20] = [
+        ("paddle", 0xf500c8236eef9b25),
+        ("wall", 0x5c56553eaa599f6c),
+        ("brick-0", 0xc28c8963687e65a5),
+        ("brick-1", 0x40aac112af6a4a80),
+        ("brick-2", 0x701454b432770502),
+        ("brick-3", 0x17151d1095455cb3),
+        ("brick-4", 0x89ec398be5cf5462),
+        ("brick-5", 0x4fee7447fdae6c2b),
+        ("brick-6", 0x15f02c9642c285be),
+        ("brick-7", 0xa4cc8bb0ff5bddc2),
+        ("brick-8", 0x7c54ba119d964c3a),
+        ("brick-9", 0x5b53866aac089613),
+        ("brick-10", 0xde8281d97fc8f644),
+        ("brick-11", 0x4733e0c9a85a8703),
+        ("brick-12", 0x848629aa1582f767),
+        ("brick-13", 0xcc5be7c246ee0b9e),
+        ("brick-14", 0xa1fea44bb3be1d44),
+        ("brick-15", 0xacec79523f9ca0dd),
+        ("life-lost", 0x57eb73781cd9a748),
+        ("win", 0x77c9f7ed17aeb90e),
+    ];
+
+    #[test]
+    fn sounds_match_their_golden_files() {
+        let sounds: Vec<Sound> = Sound::all().collect();
+        assert_eq!(sounds.len(), GOLDEN.len());
+        for (sound, (name, golden)) in sounds.into_iter().zip(GOLDEN) {
+            assert_eq!(sound.name(), name);
+            let mut hash = Fnv::new();
+            hash.write(&synth::wav(&sound.samples(), SAMPLE_RATE));
+            assert_eq!(hash.0, golden, "{} sounds different, it hashes to {:#018x}", name, hash.0);
+        }
+    }
+
+    #[test]
+    fn sounds_fade_out_without_clipping() {
+        for sound in Sound::all() {
+            let samples = sound.samples();
+            assert!(samples.iter().all(|sample| sample.abs() < 1.0), "{} clips", sound.name());
+            assert!(samples.iter().any(|sample| sample.abs() > 0.1), "{} is silent", sound.name());
+            assert!(samples.last().unwrap().abs() < 0.01, "{} ends with a click", sound.name());
+        }
+    }
+}
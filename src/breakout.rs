@@ -1,78 +1,91 @@
-use std::process::exit;
-
-use macroquad::color::{BLACK, Color, GREEN, ORANGE, RED, SKYBLUE, WHITE, YELLOW};
+use macroquad::color::Color;
 use macroquad::math::{Vec2, vec2};
-use macroquad::prelude::{draw_rectangle_lines, mouse_position};
-use macroquad::shapes::draw_rectangle;
-use macroquad::text::get_text_center;
-use macroquad::time::get_frame_time;
-use macroquad::ui::root_ui;
-use macroquad::window::{clear_background, screen_height, screen_width};
-
-struct Rect {
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-}
 
-impl Rect {
-    fn from_vec(pos: Vec2, size: Vec2) -> Self {
-        Self {
-            x: pos.x,
-            y: pos.y,
-            width: size.x,
-            height: size.y,
-        }
-    }
+use crate::audio::Sound;
+use crate::collision::{Hit, Rect, sweep};
+use crate::level::Level;
+use crate::powerup::{CAPSULE_SIZE, CAPSULE_SPEED, Capsule, DROP_CHANCE, EXPAND_FACTOR, Effects, LASER_COOLDOWN, LASER_SIZE, LASER_SPEED, PowerUp, SLOW_FACTOR};
+use crate::rng::Rng;
+use crate::rules::{Rules, Ruleset};
 
-    fn intersects(&self, other: &Rect) -> bool {
-        self.x < other.x + other.width
-            && self.x + self.width > other.x
-            && self.y < other.y + other.height
-            && self.y + self.height > other.y
-    }
+// game constants
+pub const BRICK_COUNT: u8 = 14;
+pub const BRICK_SIZE: Vec2 = vec2(50.0, 15.0);
+pub const BRICK_GAP: f32 = 6.0;
+pub const GAME_WIDTH: f32 = BRICK_COUNT as f32 * (BRICK_SIZE.x + BRICK_GAP) - BRICK_GAP;
+pub const GAME_HEIGHT: f32 = 960.0;
+pub const PADDING: f32 = 150.0;
+pub const BASE_SPEED: f32 = 0.5;
+pub const BALL_SIZE: Vec2 = vec2(16.0, 16.0);
+pub const PADDLE_SIZE: Vec2 = vec2(50.0, 15.0);
+/// The smallest playfield a game can be played on: wide enough for an expanded paddle and tall
+/// enough for a served ball to start above the paddle.
+pub const MIN_SIZE: Vec2 = vec2(PADDLE_SIZE.x * EXPAND_FACTOR, 2.0 * (PADDING + BALL_SIZE.y));
+
+// physics runs at a fixed rate regardless of frame rate
+pub const TICK_RATE: u32 = 240;
+pub const TICK: f32 = 1.0 / TICK_RATE as f32;
+// longest frame that is simulated in full, so a stall doesn't cause a burst of catch-up ticks
+const MAX_FRAME_TIME: f32 = 0.25;
+// upper bound on contacts resolved in one tick, in case the ball gets wedged
+const MAX_BOUNCES: u8 = 8;
+// the multi-ball power-up stops splitting balls past this many
+const MAX_BALLS: usize = 12;
+// degrees between the balls split off by multi-ball
+const SPLIT_ANGLE: f32 = 20.0;
+/// Most players that can take turns at one game.
+pub const MAX_PLAYERS: u8 = 2;
+/// Steepest bounce off the paddle in degrees from vertical, short of sending the ball sideways
+/// for good.
+pub const MAX_ANGLE: f32 = 80.0;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BrickKind {
+    /// Breaks after its hits run out, getting darker with each one.
+    #[default]
+    Normal,
+    /// Never breaks, and doesn't need to be cleared to finish the wall.
+    Steel,
+    /// Takes its neighbours with it when it breaks.
+    Explosive,
 }
 
-// game constants
-const BRICK_COUNT: u8 = 14;
-const BRICK_SIZE: Vec2 = vec2(50.0, 15.0);
-const BRICK_GAP: f32 = 6.0;
-const BRICK_ROWS: u8 = 8;
-const GAME_WIDTH: f32 = BRICK_COUNT as f32 * (BRICK_SIZE.x + BRICK_GAP) - BRICK_GAP;
-const PADDING: f32 = 150.0;
-const BASE_SPEED: f32 = 0.5;
-const BALL_SIZE: Vec2 = vec2(16.0, 16.0);
-
-#[derive(PartialEq)]
+#[derive(Clone, PartialEq)]
 pub struct Brick {
-    pos: Vec2,
-    row: u8,
+    pub pos: Vec2,
+    pub row: u8,
+    pub kind: BrickKind,
+    pub color: Color,
+    /// Hits left before the brick breaks.
+    pub hits: u8,
+    pub max_hits: u8,
+    pub points: u16,
 }
 
 impl Brick {
-    fn new(row: u8, col: u8) -> Self {
-        let x = col as f32 * (BRICK_SIZE.x + BRICK_GAP);
-        let y = PADDING + (BRICK_ROWS - row - 1) as f32 * (BRICK_SIZE.y + BRICK_GAP);
-
-        Self {
-            pos: vec2(x, y),
-            row,
+    /// The brick's color, darkened by the damage it has taken so far.
+    pub fn color(&self) -> Color {
+        if self.kind == BrickKind::Steel || self.hits == self.max_hits {
+            return self.color;
         }
+        let shade = 0.4 + 0.6 * self.hits as f32 / self.max_hits as f32;
+        Color::new(self.color.r * shade, self.color.g * shade, self.color.b * shade, self.color.a)
     }
 
-    fn color(&self) -> Color {
-        match self.row {
-            0 | 1 => YELLOW,
-            2 | 3 => GREEN,
-            4 | 5 => ORANGE,
-            6 | 7 => RED,
-            _ => Color::default()
-        }
+    pub fn point_value(&self) -> u16 {
+        self.points
     }
 
-    fn point_value(&self) -> u16 {
-        ((self.row / 2) as f64).floor() as u16 * 2 + 1
+    /// Whether the brick has to be broken to clear the wall.
+    pub fn breakable(&self) -> bool {
+        self.kind != BrickKind::Steel
+    }
+
+    /// Whether two bricks sit next to each other in the wall, including diagonally.
+    fn touches(&self, other: &Brick) -> bool {
+        let reach = BRICK_SIZE + BRICK_GAP * 1.5;
+        let distance = (self.pos - other.pos).abs();
+        distance.x < reach.x && distance.y < reach.y
     }
 }
 
@@ -85,188 +98,1114 @@ pub enum GameState {
     Win
 }
 
+/// How the host wants the paddle moved this frame.
+pub enum PaddleInput {
+    /// Move the paddle's left edge to an absolute x position in the playfield.
+    Target(f32),
+    /// Move the paddle by a relative amount, e.g. a mouse delta.
+    Delta(f32),
+}
+
+/// Everything the simulation needs from the outside world for one update.
+pub struct Input {
+    pub paddle: PaddleInput,
+    /// Launches a caught ball, or fires the lasers.
+    pub fire: bool,
+    /// Elapsed time since the last update, in seconds.
+    pub dt: f32,
+}
+
+/// The input applied at the start of a single tick. A game is fully determined by its seed and
+/// the sequence of these, which is what replays store.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TickInput {
+    /// Where the paddle's left edge should be. It is clamped to the playfield.
+    pub paddle_x: f32,
+    pub fire: bool,
+}
+
+/// Starting conditions that can be changed from the defaults. They change how a game plays, so
+/// replays record them along with the seed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+    /// Balls to start with.
+    pub lives: u8,
+    /// Multiplies the ball speed the rules call for.
+    pub speed: f32,
+    pub deflection: Deflection,
+    /// Players taking turns, from 1 to `MAX_PLAYERS`.
+    pub players: u8,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self { lives: 3, speed: 1.0, deflection: Deflection::default(), players: 1 }
+    }
+}
+
+/// A player's part of a game for two: what they have left of their own wall and their own
+/// score and balls. The player waiting for their turn keeps theirs here.
+#[derive(Clone)]
+pub struct Turn {
+    pub bricks: Vec<Brick>,
+    pub rules: Rules,
+    pub score: u16,
+    pub balls_rem: u8,
+    pub game_count: u8,
+}
+
+impl Turn {
+    /// Whether the player is out of balls or has cleared every wall.
+    pub fn finished(&self, ruleset: Ruleset) -> bool {
+        self.balls_rem == 0 || self.game_count >= ruleset.walls()
+    }
+}
+
+/// How a game ended, taken on the tick it ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub score: u16,
+    pub balls_rem: u8,
+    /// `Breakout::state_hash` at the end.
+    pub hash: u64,
+}
+
+/// Bounce angles off the paddle in degrees from vertical. A ball hitting the middle of the paddle
+/// leaves at `min_angle`, and the angle grows towards `max_angle` at either end.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Deflection {
+    pub min_angle: f32,
+    pub max_angle: f32,
+}
+
+impl Default for Deflection {
+    fn default() -> Self {
+        Self {
+            min_angle: 15.0,
+            max_angle: 60.0,
+        }
+    }
+}
+
+impl Deflection {
+    /// Whether the angles are in order and no more than `MAX_ANGLE`.
+    pub fn valid(&self) -> bool {
+        0.0 <= self.min_angle && self.min_angle <= self.max_angle && self.max_angle <= MAX_ANGLE
+    }
+
+    /// The velocity of a ball leaving the paddle, keeping the speed of `vel`. `offset` is where
+    /// the ball hit, from -1 at the left end of the paddle to 1 at the right end. The sine and
+    /// cosine come from `libm`, as the standard library's can differ in the last bit between
+    /// platforms and a game has to play out the same on every machine.
+    pub fn bounce(&self, vel: Vec2, offset: f32) -> Vec2 {
+        let offset = offset.clamp(-1.0, 1.0);
+        let angle = (self.min_angle + offset.abs() * (self.max_angle - self.min_angle)).to_radians();
+        // a dead-centre hit keeps the ball's horizontal direction
+        let side = if offset == 0.0 { vel.x.signum() } else { offset.signum() };
+        vec2(side * libm::sinf(angle), -libm::cosf(angle)) * vel.length()
+    }
+}
+
+#[derive(Clone)]
+pub struct Ball {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub prev_pos: Vec2,
+    pub hit_paddle: bool,
+    /// Where the ball sits on the paddle while it is caught, relative to the paddle's left edge.
+    pub caught: Option<f32>,
+}
+
+impl Ball {
+    pub fn new(pos: Vec2, vel: Vec2) -> Self {
+        Self {
+            pos,
+            vel,
+            prev_pos: pos,
+            hit_paddle: false,
+            caught: None,
+        }
+    }
+
+    /// The position interpolated between the last two ticks, for smooth rendering.
+    pub fn render_pos(&self, alpha: f32) -> Vec2 {
+        self.prev_pos.lerp(self.pos, alpha)
+    }
+
+    fn rect(&self) -> Rect {
+        Rect::from_vec(self.pos, BALL_SIZE)
+    }
+}
+
+/// Something the ball touched during a tick.
+enum Contact {
+    Wall,
+    Ceiling,
+    Paddle,
+    Brick(usize),
+}
+
+#[derive(Clone)]
 pub struct Breakout {
-    pub font_size: u16,
+    pub size: Vec2,
+    pub ruleset: Ruleset,
+    pub level: Level,
+    pub tuning: Tuning,
     pub game_state: GameState,
     pub bricks: Vec<Brick>,
-    pub ball_pos: Vec2,
-    pub ball_vel: Vec2,
+    pub balls: Vec<Ball>,
     pub paddle_pos: Vec2,
-    pub hit_paddle: bool,
-    pub last_mouse_x: f32,
+    pub rules: Rules,
+    pub capsules: Vec<Capsule>,
+    pub lasers: Vec<Vec2>,
+    pub effects: Effects,
     pub score: u16,
     pub balls_rem: u8,
     pub game_count: u8,
+    /// Whose turn it is, from 0.
+    pub player: u8,
+    /// The other player's wall, score and balls in a game for two. They are swapped with the
+    /// ones in play whenever a ball is lost.
+    pub waiting: Option<Turn>,
+    pub seed: u64,
+    pub rng: Rng,
+    pub ticks: u64,
+    /// The input of every tick played since `start`.
+    pub inputs: Vec<TickInput>,
+    /// Set on the tick the game is lost or won.
+    pub outcome: Option<Outcome>,
+    /// Sounds made during play that the frontend hasn't taken yet.
+    pub sounds: Vec<Sound>,
+    pub(crate) paddle_target: f32,
+    fire_pending: bool,
+    pub(crate) laser_cooldown: u32,
+    accumulator: f32,
 }
 
 impl Breakout {
-    pub fn new(font_size: u16) -> Self {
+    /// Creates a new game on a playfield of the given size, normally `GAME_WIDTH` by
+    /// `GAME_HEIGHT`. Games with the same seed and inputs play out identically. Panics if the
+    /// size isn't `playable`.
+    pub fn new(size: Vec2, ruleset: Ruleset, level: Level, seed: u64) -> Self {
+        assert!(Breakout::playable(size), "playfield size {} is too small or not finite", size);
+        let paddle_x = (size.x - PADDLE_SIZE.x) / 2.0;
         Self {
-            font_size,
+            size,
+            ruleset,
+            bricks: level.bricks(),
+            level,
+            tuning: Tuning::default(),
             game_state: GameState::NewGame,
-            bricks: Breakout::bricks(),
-            ball_pos: vec2(GAME_WIDTH / 2.0, screen_height() / 2.0),
-            ball_vel: vec2(BASE_SPEED, BASE_SPEED),
-            paddle_pos: vec2((GAME_WIDTH - BRICK_SIZE.x) / 2.0, screen_height() - PADDING),
-            hit_paddle: false,
-            last_mouse_x: 0.0,
+            balls: vec![Ball::new(vec2(size.x / 2.0, size.y / 2.0), vec2(BASE_SPEED, BASE_SPEED))],
+            paddle_pos: vec2(paddle_x, size.y - PADDING),
+            rules: Rules::default(),
+            capsules: Vec::new(),
+            lasers: Vec::new(),
+            effects: Effects::default(),
             score: 0,
-            balls_rem: 3,
+            balls_rem: Tuning::default().lives,
             game_count: 0,
+            player: 0,
+            waiting: None,
+            seed,
+            rng: Rng::new(seed),
+            ticks: 0,
+            inputs: Vec::new(),
+            outcome: None,
+            sounds: Vec::new(),
+            paddle_target: paddle_x,
+            fire_pending: false,
+            laser_cooldown: 0,
+            accumulator: 0.0,
         }
     }
 
-    fn bricks() -> Vec<Brick> {
-        let mut list = Vec::new();
-        for row in 0..BRICK_ROWS {
-            for col in 0..BRICK_COUNT {
-                list.push(Brick::new(row, col))
-            }
+    /// Whether a game can be played on a playfield of this size: finite and at least
+    /// `MIN_SIZE`.
+    pub fn playable(size: Vec2) -> bool {
+        size.is_finite() && size.cmpge(MIN_SIZE).all()
+    }
+
+    pub fn with_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = tuning;
+        self.balls_rem = tuning.lives;
+        self.waiting = (tuning.players > 1).then(|| Turn {
+            bricks: self.level.bricks(),
+            rules: Rules::default(),
+            score: 0,
+            balls_rem: tuning.lives,
+            game_count: 0,
+        });
+        self
+    }
+
+    /// A player's score, whether or not it is their turn.
+    pub fn player_score(&self, player: u8) -> u16 {
+        match &self.waiting {
+            Some(turn) if player != self.player => turn.score,
+            _ => self.score,
         }
-        list
     }
 
-    pub fn exit_button(&self) {
-        let text = "Exit Game";
-        if root_ui().button(vec2(screen_width() / 2.0 - self.text_center(text).x, screen_height() - 300.0), text) {
-            exit(0)
+    /// A player's balls left, whether or not it is their turn.
+    pub fn player_balls(&self, player: u8) -> u8 {
+        match &self.waiting {
+            Some(turn) if player != self.player => turn.balls_rem,
+            _ => self.balls_rem,
         }
     }
 
-    pub fn update(&mut self) {
-        self.handle_mouse_move();
-        self.check_wall_collision();
-        self.check_paddle_collision();
-        self.check_brick_collision();
-        self.update_ball();
+    /// The player with the higher score in a game for two, or `None` for a draw or a game for
+    /// one.
+    pub fn winner(&self) -> Option<u8> {
+        self.waiting.as_ref()?;
+        let (first, second) = (self.player_score(0), self.player_score(1));
+        match first.cmp(&second) {
+            std::cmp::Ordering::Greater => Some(0),
+            std::cmp::Ordering::Less => Some(1),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
 
-        if self.bricks.len() == 0 {
-            self.game_state = GameState::Win;
+    /// The paddle's collision box. Outside of play the paddle spans the whole playfield.
+    pub fn paddle_rect(&self) -> Rect {
+        if self.game_state == GameState::Playing {
+            Rect::from_vec(self.paddle_pos, vec2(self.paddle_width(), PADDLE_SIZE.y))
+        } else {
+            Rect { x: 0.0, y: self.paddle_pos.y, width: self.size.x, height: PADDLE_SIZE.y }
         }
     }
 
-    fn handle_mouse_move(&mut self) {
-        let mouse_x = mouse_position().0;
-        let delta = mouse_x - self.last_mouse_x;
-        self.paddle_pos.x = f32::min(f32::max(self.paddle_pos.x + delta, 0.0), GAME_WIDTH - BRICK_SIZE.x);
-        self.last_mouse_x = mouse_x;
+    /// Width of the paddle in play, after the arcade rules and power-ups.
+    pub fn paddle_width(&self) -> f32 {
+        if self.effects.is_active(PowerUp::Expand) {
+            self.rules.paddle_width() * EXPAND_FACTOR
+        } else {
+            self.rules.paddle_width()
+        }
     }
 
-    fn check_wall_collision(&mut self) {
-        if self.ball_pos.x <= 0.0 || self.ball_pos.x >= GAME_WIDTH - BALL_SIZE.x {
-            self.ball_vel.x *= -1.0;
+    /// Ball speed in pixels per millisecond, after the arcade rules, tuning and power-ups.
+    pub fn ball_speed(&self) -> f32 {
+        let speed = self.rules.ball_speed() * self.tuning.speed;
+        if self.effects.is_active(PowerUp::Slow) {
+            speed * SLOW_FACTOR
+        } else {
+            speed
         }
-        if self.ball_pos.y <= 0.0 {
-            self.ball_vel.y *= -1.0;
+    }
+
+    /// Applies the input and advances the physics by as many whole ticks as fit in `input.dt`.
+    /// Leftover time is carried over to the next update.
+    pub fn update(&mut self, input: &Input) {
+        self.paddle_target = match input.paddle {
+            PaddleInput::Target(x) => x,
+            PaddleInput::Delta(delta) => self.paddle_target + delta,
+        };
+        self.paddle_target = self.paddle_target.clamp(0.0, self.size.x - self.paddle_width());
+        self.fire_pending |= input.fire;
+
+        for _ in 0..self.advance(input.dt) {
+            let fire = std::mem::take(&mut self.fire_pending);
+            self.step(TickInput { paddle_x: self.paddle_target, fire });
         }
     }
 
-    fn check_paddle_collision(&mut self) {
-        let ball_rect = Rect::from_vec(self.ball_pos, BALL_SIZE);
-        let paddle_rect = if self.game_state == GameState::Playing {
-            Rect::from_vec(self.paddle_pos, BRICK_SIZE)
-        } else {
-            Rect { x: 0.0, y: self.paddle_pos.y, width: GAME_WIDTH, height: BRICK_SIZE.y }
+    /// Adds `dt` seconds to the clock and returns how many ticks are now due. Hosts that supply
+    /// their own `TickInput`s, like replays, call `step` that many times.
+    pub fn advance(&mut self, dt: f32) -> u32 {
+        self.accumulator = f32::min(self.accumulator + dt, MAX_FRAME_TIME);
+        let mut ticks = 0;
+        while self.accumulator >= TICK {
+            self.accumulator -= TICK;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// Leaves the attract mode and starts play with a freshly served ball, so that everything
+    /// from here on depends only on the seed and the tick inputs.
+    pub fn start(&mut self) {
+        self.game_state = GameState::Playing;
+        self.serve();
+        self.ticks = 0;
+        self.inputs.clear();
+        self.outcome = None;
+    }
+
+    /// Applies one tick's input and advances the physics by exactly one fixed step of `TICK`
+    /// seconds.
+    pub fn step(&mut self, input: TickInput) {
+        let playing = self.game_state == GameState::Playing;
+        if playing {
+            self.inputs.push(input);
+        }
+        self.move_paddle(input.paddle_x);
+        if input.fire && playing {
+            self.fire();
+        }
+        self.tick();
+        // the attract mode keeps ticking after the end, so the outcome has to be caught here
+        if playing && self.game_state != GameState::Playing {
+            self.outcome = Some(Outcome { score: self.score, balls_rem: self.balls_rem, hash: self.state_hash() });
+        }
+    }
+
+    /// A hash of everything that affects how the game plays on from here, for checking that a
+    /// replay plays out the same. It is 64-bit FNV-1a over little-endian fields, so it is the
+    /// same on every platform.
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        let vec = |hash: &mut Fnv, v: Vec2| {
+            hash.write(&v.x.to_le_bytes());
+            hash.write(&v.y.to_le_bytes());
         };
-        if ball_rect.intersects(&paddle_rect) {
-            if !self.hit_paddle {
-                self.hit_paddle = true;
-                self.ball_vel.y *= -1.0;
+        hash.write(&[self.game_state.clone() as u8, self.balls_rem, self.game_count]);
+        hash.write(&self.score.to_le_bytes());
+        hash.write(&self.ticks.to_le_bytes());
+        // the generator's state is private, but its next output identifies it just as well
+        hash.write(&self.rng.clone().next_u64().to_le_bytes());
+        hash.write(&self.rules.hits.to_le_bytes());
+        hash.write(&[self.rules.hit_orange as u8, self.rules.hit_red as u8, self.rules.hit_ceiling as u8]);
+        vec(&mut hash, self.paddle_pos);
+        hash.write(&self.laser_cooldown.to_le_bytes());
+        for len in [self.balls.len(), self.bricks.len(), self.capsules.len(), self.lasers.len(), self.effects.active.len()] {
+            hash.write(&(len as u32).to_le_bytes());
+        }
+        for ball in &self.balls {
+            vec(&mut hash, ball.pos);
+            vec(&mut hash, ball.vel);
+            hash.write(&[ball.hit_paddle as u8]);
+            hash.write(&ball.caught.map_or(-1.0, |offset| offset).to_le_bytes());
+        }
+        for brick in &self.bricks {
+            vec(&mut hash, brick.pos);
+            hash.write(&[brick.hits]);
+        }
+        for capsule in &self.capsules {
+            vec(&mut hash, capsule.pos);
+            hash.write(&[capsule.kind as u8]);
+        }
+        for &laser in &self.lasers {
+            vec(&mut hash, laser);
+        }
+        for &(kind, ticks) in &self.effects.active {
+            hash.write(&[kind as u8]);
+            hash.write(&ticks.to_le_bytes());
+        }
+        // only games for two have these, so games for one hash as they always have
+        if let Some(turn) = &self.waiting {
+            hash.write(&[self.player, turn.balls_rem, turn.game_count]);
+            hash.write(&turn.score.to_le_bytes());
+            hash.write(&turn.rules.hits.to_le_bytes());
+            hash.write(&[turn.rules.hit_orange as u8, turn.rules.hit_red as u8, turn.rules.hit_ceiling as u8]);
+            hash.write(&(turn.bricks.len() as u32).to_le_bytes());
+            for brick in &turn.bricks {
+                vec(&mut hash, brick.pos);
+                hash.write(&[brick.hits]);
+            }
+        }
+        hash.0
+    }
+
+    fn tick(&mut self) {
+        for i in 0..self.balls.len() {
+            let ball = &mut self.balls[i];
+            ball.prev_pos = ball.pos;
+            if let Some(offset) = ball.caught {
+                ball.pos = vec2(self.paddle_pos.x + offset, self.paddle_pos.y - BALL_SIZE.y);
+            } else {
+                self.check_paddle_overlap(i);
+                self.move_ball(i);
+            }
+        }
+        self.move_capsules();
+        self.move_lasers();
+        self.tick_effects();
+        self.check_ball_lost();
+        self.ticks += 1;
+
+        // a finished game keeps ticking behind the results, and its empty wall is not cleared
+        // again on every tick
+        if self.game_state == GameState::Playing && !self.bricks.iter().any(Brick::breakable) {
+            self.wall_cleared();
+        }
+    }
+
+    /// Ends the game, or puts up the next wall if the ruleset has more than one. A player who
+    /// clears their last wall while the other is still going hands over to them.
+    fn wall_cleared(&mut self) {
+        self.game_count += 1;
+        if self.game_count >= self.ruleset.walls() {
+            self.sounds.push(Sound::Win);
+            if self.other_player_playing() {
+                self.effects.clear();
+                self.capsules.clear();
+                self.lasers.clear();
+                self.swap_turns();
+                self.serve();
+            } else {
+                self.game_state = GameState::Win;
+            }
+            return;
+        }
+        self.bricks = self.level.bricks();
+        self.rules = Rules::default();
+        self.capsules.clear();
+        self.lasers.clear();
+        self.serve();
+    }
+
+    /// How far between the previous and the current tick the simulation is, from 0 to 1.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / TICK
+    }
+
+    /// Adds another ball to the game, e.g. for multi-ball.
+    pub fn spawn_ball(&mut self, pos: Vec2, vel: Vec2) {
+        self.balls.push(Ball::new(pos, vel));
+    }
+
+    fn move_paddle(&mut self, x: f32) {
+        self.paddle_pos.x = x.clamp(0.0, self.size.x - self.paddle_width());
+    }
+
+    /// Releases caught balls, or fires a pair of lasers from the ends of the paddle.
+    fn fire(&mut self) {
+        if self.release_balls() {
+            return;
+        }
+        if self.effects.is_active(PowerUp::Laser) && self.laser_cooldown == 0 {
+            let y = self.paddle_pos.y - LASER_SIZE.y;
+            self.lasers.push(vec2(self.paddle_pos.x, y));
+            self.lasers.push(vec2(self.paddle_pos.x + self.paddle_width() - LASER_SIZE.x, y));
+            self.laser_cooldown = LASER_COOLDOWN;
+        }
+    }
+
+    fn move_lasers(&mut self) {
+        self.laser_cooldown = self.laser_cooldown.saturating_sub(1);
+        let mut i = 0;
+        while i < self.lasers.len() {
+            self.lasers[i].y -= LASER_SPEED * TICK * 1000.0;
+            let laser_rect = Rect::from_vec(self.lasers[i], LASER_SIZE);
+            let target = self.bricks.iter().position(|brick| laser_rect.intersects(&Rect::from_vec(brick.pos, BRICK_SIZE)));
+            if let Some(brick) = target {
+                self.sounds.push(Sound::Brick(self.bricks[brick].row));
+                self.break_bricks(vec![brick]);
+            }
+            if target.is_some() || self.lasers[i].y + LASER_SIZE.y < 0.0 {
+                self.lasers.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    fn move_capsules(&mut self) {
+        let paddle = self.paddle_rect();
+        let mut caught = Vec::new();
+        self.capsules.retain_mut(|capsule| {
+            capsule.pos.y += CAPSULE_SPEED * TICK * 1000.0;
+            if Rect::from_vec(capsule.pos, CAPSULE_SIZE).intersects(&paddle) {
+                caught.push(capsule.kind);
+                return false;
+            }
+            capsule.pos.y < paddle.y + paddle.height
+        });
+        for kind in caught {
+            self.collect(kind);
+        }
+    }
+
+    /// Lets go of every caught ball. Returns true if any were caught.
+    fn release_balls(&mut self) -> bool {
+        let mut released = false;
+        for ball in &mut self.balls {
+            released |= ball.caught.take().is_some();
+        }
+        released
+    }
+
+    /// Splits every ball into three, fanned out around its heading.
+    fn split_balls(&mut self) {
+        let mut spawned = Vec::new();
+        for ball in &self.balls {
+            if self.balls.len() + spawned.len() + 2 > MAX_BALLS {
+                break;
+            }
+            for angle in [-SPLIT_ANGLE, SPLIT_ANGLE] {
+                // `libm` rather than `Vec2::from_angle`, for the same result on every platform
+                let angle = angle.to_radians();
+                let mut vel = vec2(libm::cosf(angle), libm::sinf(angle)).rotate(ball.vel);
+                // balls split off a caught ball are sent upwards straight away
+                if ball.caught.is_some() {
+                    vel.y = -vel.y.abs();
+                }
+                spawned.push(Ball::new(ball.pos, vel));
+            }
+        }
+        self.balls.extend(spawned);
+    }
+
+    fn collect(&mut self, kind: PowerUp) {
+        match kind.duration() {
+            Some(ticks) => self.effects.activate(kind, ticks),
+            None => self.balls_rem = self.balls_rem.saturating_add(1),
+        }
+        match kind {
+            PowerUp::Slow => self.apply_ball_speed(),
+            PowerUp::Expand => self.move_paddle(self.paddle_pos.x),
+            PowerUp::MultiBall => self.split_balls(),
+            _ => {}
+        }
+    }
+
+    fn tick_effects(&mut self) {
+        for kind in self.effects.tick() {
+            match kind {
+                PowerUp::Slow => self.apply_ball_speed(),
+                PowerUp::Catch => {
+                    self.release_balls();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Bounces the ball up if the paddle was moved into it, since the sweep in `move_ball` only
+    /// catches the ball moving into the paddle.
+    fn check_paddle_overlap(&mut self, i: usize) {
+        if self.balls[i].rect().intersects(&self.paddle_rect()) {
+            if !self.balls[i].hit_paddle {
+                self.balls[i].hit_paddle = true;
+                self.deflect_off_paddle(i);
             }
         } else {
-            self.hit_paddle = false;
+            self.balls[i].hit_paddle = false;
         }
     }
 
-    fn check_brick_collision(&mut self) {
-        for (i, brick) in self.bricks.iter().enumerate() {
-            let ball_rect = Rect::from_vec(self.ball_pos, BALL_SIZE);
-            let brick_rect = Rect::from_vec(brick.pos, BRICK_SIZE);
-            if ball_rect.intersects(&brick_rect) {
-                self.ball_vel.y *= -1.0;
-                if self.game_state == GameState::Playing {
-                    self.score += brick.point_value();
-                    self.bricks.remove(i);
+    /// Moves the ball through one tick, bouncing off everything it touches on the way. Each
+    /// contact uses up part of the movement and the rest continues with the reflected velocity.
+    fn move_ball(&mut self, i: usize) {
+        let mut remaining = 1.0;
+        for _ in 0..MAX_BOUNCES {
+            let delta = self.balls[i].vel * TICK * 1000.0 * remaining;
+            let ball_rect = self.balls[i].rect();
+
+            let mut first: Option<Hit> = None;
+            let mut contacts = Vec::new();
+            let mut consider = |hit: Hit, contact: Contact| {
+                match first {
+                    Some(ref mut current) if current.simultaneous(&hit) => current.merge(&hit),
+                    Some(current) if current.time < hit.time => return,
+                    _ => {
+                        first = Some(hit);
+                        contacts.clear();
+                    }
+                }
+                contacts.push(contact);
+            };
+
+            let [left, top, right] = self.walls();
+            for (wall, contact) in [(left, Contact::Wall), (top, Contact::Ceiling), (right, Contact::Wall)] {
+                if let Some(hit) = sweep(&ball_rect, delta, &wall) {
+                    consider(hit, contact);
+                }
+            }
+            if let Some(hit) = sweep(&ball_rect, delta, &self.paddle_rect()) {
+                consider(hit, Contact::Paddle);
+            }
+            for (j, brick) in self.bricks.iter().enumerate() {
+                if let Some(hit) = sweep(&ball_rect, delta, &Rect::from_vec(brick.pos, BRICK_SIZE)) {
+                    consider(hit, Contact::Brick(j));
+                }
+            }
+
+            let ball = &mut self.balls[i];
+            let Some(hit) = first else {
+                ball.pos += delta;
+                return;
+            };
+            ball.pos += delta * hit.time;
+            ball.vel = hit.reflect(ball.vel);
+            remaining *= 1.0 - hit.time;
+            self.resolve_contacts(i, &hit, contacts);
+            if self.balls[i].caught.is_some() {
+                return;
+            }
+        }
+    }
+
+    fn resolve_contacts(&mut self, i: usize, hit: &Hit, contacts: Vec<Contact>) {
+        let playing = self.game_state == GameState::Playing;
+        let mut hit_bricks = Vec::new();
+        for contact in contacts {
+            if playing {
+                self.sounds.push(match contact {
+                    Contact::Wall | Contact::Ceiling => Sound::Wall,
+                    Contact::Paddle => Sound::Paddle,
+                    Contact::Brick(j) => Sound::Brick(self.bricks[j].row),
+                });
+            }
+            match contact {
+                Contact::Wall => {}
+                Contact::Ceiling => {
+                    if playing {
+                        self.rules.ceiling_hit();
+                    }
+                }
+                // only the top face aims the ball, a side hit just bounces off
+                Contact::Paddle if hit.normal.y < 0.0 => {
+                    self.deflect_off_paddle(i);
+                    if self.effects.is_active(PowerUp::Catch) && playing {
+                        self.balls[i].caught = Some(self.balls[i].pos.x - self.paddle_pos.x);
+                    }
+                }
+                Contact::Paddle => {}
+                Contact::Brick(j) => hit_bricks.push(j),
+            }
+        }
+        if playing {
+            for &j in &hit_bricks {
+                // `row` counts up from the bottom of the wall
+                let from_top = (self.level.cells.len() - 1) as u8 - self.bricks[j].row;
+                if self.rules.brick_hit(from_top) {
+                    self.apply_ball_speed();
                 }
-                break;
             }
+            self.break_bricks(hit_bricks);
         }
     }
 
-    fn update_ball(&mut self) {
-        self.ball_pos += self.ball_vel * get_frame_time() * 1000.0;
+    /// Sends the ball back up at an angle set by where it hit the paddle.
+    fn deflect_off_paddle(&mut self, i: usize) {
+        let paddle = self.paddle_rect();
+        let paddle_centre = paddle.x + paddle.width / 2.0;
+        let ball = &mut self.balls[i];
+        let ball_centre = ball.pos.x + BALL_SIZE.x / 2.0;
+        let reach = (paddle.width + BALL_SIZE.x) / 2.0;
+        ball.vel = self.tuning.deflection.bounce(ball.vel, (ball_centre - paddle_centre) / reach);
+    }
+
+    /// Solid areas just outside the left, top and right edges of the playfield.
+    fn walls(&self) -> [Rect; 3] {
+        let (w, h) = (self.size.x, self.size.y);
+        [
+            Rect { x: -w, y: -h, width: w, height: h * 3.0 },
+            Rect { x: -w, y: -h, width: w * 3.0, height: h },
+            Rect { x: w, y: -h, width: w, height: h * 3.0 },
+        ]
+    }
+
+    fn break_bricks(&mut self, indices: Vec<usize>) {
+        let mut destroyed = Vec::new();
+        for i in indices {
+            let brick = &mut self.bricks[i];
+            if brick.breakable() {
+                brick.hits -= 1;
+                if brick.hits == 0 {
+                    destroyed.push(i);
+                }
+            }
+        }
+
+        // explosions can set each other off, so keep going until no new bricks are caught
+        let mut pending = destroyed.clone();
+        while let Some(i) = pending.pop() {
+            if self.bricks[i].kind != BrickKind::Explosive {
+                continue;
+            }
+            for (j, other) in self.bricks.iter().enumerate() {
+                if other.breakable() && !destroyed.contains(&j) && self.bricks[i].touches(other) {
+                    destroyed.push(j);
+                    pending.push(j);
+                }
+            }
+        }
+
+        // remove from the back so earlier indices stay valid
+        destroyed.sort_unstable();
+        for i in destroyed.into_iter().rev() {
+            let brick = self.bricks.remove(i);
+            // custom levels can make bricks worth up to `u16::MAX` each
+            self.score = self.score.saturating_add(brick.point_value());
+            if let Some(max_score) = self.ruleset.max_score() {
+                self.score = self.score.min(max_score);
+            }
+            self.drop_capsule(&brick);
+        }
+    }
+
+    /// Sometimes releases a random power-up from a destroyed brick.
+    fn drop_capsule(&mut self, brick: &Brick) {
+        if self.rng.below(DROP_CHANCE) != 0 {
+            return;
+        }
+        let kind = PowerUp::ALL[self.rng.below(PowerUp::ALL.len() as u32) as usize];
+        let pos = brick.pos + (BRICK_SIZE - CAPSULE_SIZE) / 2.0;
+        self.capsules.push(Capsule { pos, kind });
+    }
+
+    /// Rescales every ball's velocity to the speed the rules currently call for.
+    fn apply_ball_speed(&mut self) {
+        let speed = self.ball_speed();
+        for ball in &mut self.balls {
+            ball.vel = ball.vel.normalize_or_zero() * speed;
+        }
+    }
+
+    /// Puts a single ball back in the middle of the playfield at the starting speed.
+    fn serve(&mut self) {
+        let pos = vec2(self.size.x / 2.0, self.size.y / 2.0);
+        self.balls = vec![Ball::new(pos, vec2(BASE_SPEED, BASE_SPEED))];
+        self.rules.new_serve();
+        self.apply_ball_speed();
+    }
 
-        if self.ball_pos.y >= self.paddle_pos.y {
-            self.ball_pos = vec2(GAME_WIDTH / 2.0, screen_height() / 2.0);
+    /// Drops balls that got past the paddle. A life is only lost once the last one is gone.
+    fn check_ball_lost(&mut self) {
+        let paddle_y = self.paddle_pos.y;
+        self.balls.retain(|ball| ball.pos.y < paddle_y);
+        if self.balls.is_empty() {
+            // power-ups don't carry over to the next ball
+            self.effects.clear();
+            self.capsules.clear();
+            self.lasers.clear();
             self.balls_rem -= 1;
+            if self.game_state == GameState::Playing {
+                self.sounds.push(Sound::LifeLost);
+            }
+            if self.other_player_playing() {
+                self.swap_turns();
+            }
+            self.serve();
             if self.balls_rem == 0 {
                 self.game_state = GameState::GameOver;
             }
         }
     }
 
-    pub fn draw(&self) {
-        clear_background(BLACK);
-        let offset = (screen_width() - GAME_WIDTH) / 2.0;
+    /// Whether there is a player waiting who still has balls and walls left.
+    fn other_player_playing(&self) -> bool {
+        self.waiting.as_ref().is_some_and(|turn| !turn.finished(self.ruleset))
+    }
 
-        // border
-        draw_rectangle_lines(offset - 8.0, 0.0, GAME_WIDTH + 16.0, screen_height(), 16.0, WHITE);
-        // paddle
-        if self.game_state == GameState::Playing {
-            draw_rectangle(offset + self.paddle_pos.x, self.paddle_pos.y, BRICK_SIZE.x, BRICK_SIZE.y, SKYBLUE);
-        } else {
-            draw_rectangle(offset, self.paddle_pos.y, GAME_WIDTH, BRICK_SIZE.y, SKYBLUE);
+    /// Puts the waiting player's wall, score and balls in play, and puts the current player's
+    /// aside.
+    fn swap_turns(&mut self) {
+        let Some(turn) = &mut self.waiting else {
+            return;
+        };
+        std::mem::swap(&mut self.bricks, &mut turn.bricks);
+        std::mem::swap(&mut self.rules, &mut turn.rules);
+        std::mem::swap(&mut self.score, &mut turn.score);
+        std::mem::swap(&mut self.balls_rem, &mut turn.balls_rem);
+        std::mem::swap(&mut self.game_count, &mut turn.game_count);
+        self.player = 1 - self.player;
+    }
+}
+
+/// 64-bit FNV-1a, for hashes that are the same on every platform.
+pub(crate) struct Fnv(pub(crate) u64);
+
+impl Fnv {
+    pub(crate) fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
         }
-        // ball
-        draw_rectangle(offset + self.ball_pos.x, self.ball_pos.y, BALL_SIZE.x, BALL_SIZE.y, WHITE);
+    }
+}
 
-        // bricks
-        for brick in &self.bricks {
-            draw_rectangle(offset + brick.pos.x, brick.pos.y, BRICK_SIZE.x, BRICK_SIZE.y, brick.color());
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // brick types for the test layouts
+    const BRICKS: &str = "R = color=red points=7
+Y = color=yellow points=1
+M = color=green hits=2 points=3
+S = color=gray kind=steel
+X = color=pink kind=explosive points=2
+";
+
+    /// A started single-wall game on a level with these layout rows, top row first.
+    fn game(layout: &[&str]) -> Breakout {
+        let text = format!("[bricks]\n{}[layout]\n{}\n", BRICKS, layout.join("\n"));
+        let level = Level::parse(&text).expect("test level parses");
+        let mut game = Breakout::new(vec2(GAME_WIDTH, GAME_HEIGHT), Ruleset::SingleWall, level, 1);
+        game.start();
+        game
+    }
+
+    /// Replaces the balls with one at `pos` going straight up, and plays until it comes back
+    /// down.
+    fn send_up(game: &mut Breakout, pos: Vec2) {
+        game.balls = vec![Ball::new(pos, vec2(0.0, -game.ball_speed()))];
+        for _ in 0..TICK_RATE * 5 {
+            game.step(TickInput { paddle_x: game.paddle_pos.x, fire: false });
+            if game.balls[0].vel.y > 0.0 {
+                return;
+            }
         }
+        panic!("the ball never came back down");
+    }
+
+    /// Sends a ball straight up into the brick at `index` from just below it.
+    fn hit(game: &mut Breakout, index: usize) {
+        let brick = game.bricks[index].pos;
+        send_up(game, vec2(brick.x + (BRICK_SIZE.x - BALL_SIZE.x) / 2.0, brick.y + BRICK_SIZE.y + 1.0));
+    }
+
+    /// Drops the ball past the paddle, well clear of it.
+    fn lose_ball(game: &mut Breakout) {
+        let x = if game.paddle_pos.x > game.size.x / 2.0 { 0.0 } else { game.size.x - BALL_SIZE.x };
+        let pos = vec2(x, game.paddle_pos.y + 1.0);
+        game.balls = vec![Ball::new(pos, vec2(0.0, game.ball_speed()))];
+        game.step(TickInput { paddle_x: game.paddle_pos.x, fire: false });
+    }
 
-        // score and balls rem
-        root_ui().label(vec2(offset + 16.0, 32.0), &*format!("{:03}", self.score));
-        root_ui().label(vec2(offset + GAME_WIDTH - 100.0, 32.0), &*self.balls_rem.to_string());
+    /// How many times the ball in play has sped up since the serve.
+    fn speed_ups(game: &Breakout) -> f32 {
+        let serve = BASE_SPEED * std::f32::consts::SQRT_2;
+        ((game.balls[0].vel.length() / serve - 1.0) / 0.15 * 100.0).round() / 100.0
+    }
 
-        // info text
-        match self.game_state {
-            GameState::Paused => self.draw_paused_text(),
-            GameState::NewGame => self.draw_new_game_text(),
-            GameState::GameOver => self.draw_game_over_text(),
-            GameState::Win => self.draw_win_text(),
-            GameState::Playing => {}
+    #[test]
+    fn speeds_up_after_4_and_12_hits() {
+        // below the red and orange bands
+        let mut game = game(&["..............", "..............", "..............", "..............", "YYYYYYYYYYYYYY"]);
+        for hits in 1..=12 {
+            hit(&mut game, 0);
+            let expected = match hits {
+                ..4 => 0.0,
+                4..12 => 1.0,
+                _ => 2.0,
+            };
+            assert_eq!(speed_ups(&game), expected, "after {} hits", hits);
         }
+        assert_eq!(game.score, 12);
+    }
+
+    #[test]
+    fn counts_the_red_and_orange_rows_from_the_top_of_the_wall() {
+        // three rows, so the bottom one is in the orange band
+        let mut game = game(&[".R", ".R", "Y."]);
+        hit(&mut game, 2);
+        assert_eq!(speed_ups(&game), 1.0);
+        hit(&mut game, 1);
+        assert_eq!(speed_ups(&game), 2.0);
+        // each band only speeds the ball up once
+        hit(&mut game, 0);
+        assert_eq!(speed_ups(&game), 2.0);
     }
 
-    pub fn draw_new_game_text(&self) {
-        let text = "Click anywhere to play";
-        root_ui().label(vec2(screen_width() / 2.0 - self.text_center(text).x, screen_height() / 2.0 - 64.0), text);
+    #[test]
+    fn a_new_serve_slows_down_but_the_paddle_stays_shrunk() {
+        let mut game = game(&["..............", "..............", "..............", "..............", "YYYYY........."]);
+        for _ in 0..4 {
+            hit(&mut game, 0);
+        }
+        assert_eq!(speed_ups(&game), 1.0);
+        assert_eq!(game.paddle_width(), PADDLE_SIZE.x);
+
+        send_up(&mut game, vec2(GAME_WIDTH - BALL_SIZE.x - 10.0, PADDING + 100.0));
+        assert_eq!(game.paddle_width(), PADDLE_SIZE.x / 2.0);
+
+        lose_ball(&mut game);
+        assert_eq!(game.balls_rem, 2);
+        assert_eq!(speed_ups(&game), 0.0);
+        assert_eq!(game.paddle_width(), PADDLE_SIZE.x / 2.0);
     }
 
-    pub fn draw_paused_text(&self) {
-        let text = "Game paused";
-        root_ui().label(vec2(screen_width() / 2.0 - self.text_center(text).x, screen_height() / 2.0 - 64.0), text);
-        let text = "Click anywhere to resume";
-        root_ui().label(vec2(screen_width() / 2.0 - self.text_center(text).x, screen_height() / 2.0), text);
+    #[test]
+    fn multi_hit_bricks_take_several_hits_and_steel_never_breaks() {
+        let mut game = game(&["MS.Y"]);
+        hit(&mut game, 0);
+        assert_eq!((game.bricks.len(), game.bricks[0].hits, game.score), (3, 1, 0));
+        hit(&mut game, 0);
+        assert_eq!((game.bricks.len(), game.score), (2, 3));
+        for _ in 0..3 {
+            hit(&mut game, 0);
+        }
+        assert_eq!(game.bricks[0].kind, BrickKind::Steel);
+        assert_eq!((game.bricks.len(), game.score), (2, 3));
     }
 
-    pub fn draw_game_over_text(&self) {
-        let text = "Game over!";
-        root_ui().label(vec2(screen_width() / 2.0 - self.text_center(text).x, screen_height() / 2.0 - 64.0), text);
-        let text = "Click anywhere to play again";
-        root_ui().label(vec2(screen_width() / 2.0 - self.text_center(text).x, screen_height() / 2.0), text);
+    #[test]
+    fn explosions_set_each_other_off_but_not_steel() {
+        // the yellow brick on the left is a column away from the nearest explosive
+        let mut game = game(&["Y.XXYS", "...S.."]);
+        hit(&mut game, 1);
+        let left: Vec<_> = game.bricks.iter().map(|brick| (brick.kind, brick.pos)).collect();
+        let first = game.level.bricks();
+        assert_eq!(left, [0, 4, 5].map(|i| (first[i].kind, first[i].pos)));
+        assert_eq!(game.score, 2 + 2 + 1);
     }
 
-    pub fn draw_win_text(&self) {
-        let text = "You win!";
-        root_ui().label(vec2(screen_width() / 2.0 - self.text_center(text).x, screen_height() / 2.0 - 64.0), text);
-        let text = "Click anywhere to play again";
-        root_ui().label(vec2(screen_width() / 2.0 - self.text_center(text).x, screen_height() / 2.0), text);
+    #[test]
+    fn steel_left_on_the_wall_does_not_stop_a_win() {
+        let mut game = game(&["SYS"]);
+        hit(&mut game, 1);
+        assert!(game.game_state == GameState::Win);
+        assert_eq!(game.bricks.len(), 2);
+        assert_eq!(game.outcome.map(|outcome| outcome.score), Some(1));
     }
 
-    fn text_center(&self, text: &str) -> Vec2 {
-        get_text_center(text, None, self.font_size, 1.0, 0.0)
+    /// Replaces the balls with one going straight across below the wall, where it bounces
+    /// between the side walls and never comes down.
+    fn park_ball(game: &mut Breakout) {
+        game.balls = vec![Ball::new(vec2(0.0, game.size.y / 2.0), vec2(game.ball_speed(), 0.0))];
     }
-}
 
+    /// Drops a capsule onto the middle of the paddle and plays until it is caught.
+    fn catch(game: &mut Breakout, kind: PowerUp) {
+        let paddle = game.paddle_rect();
+        let pos = vec2(paddle.x + (paddle.width - CAPSULE_SIZE.x) / 2.0, paddle.y - 50.0);
+        game.capsules.push(Capsule { pos, kind });
+        while !game.capsules.is_empty() {
+            game.step(TickInput { paddle_x: game.paddle_pos.x, fire: false });
+        }
+    }
+
+    fn wait(game: &mut Breakout, ticks: u32) {
+        for _ in 0..ticks {
+            game.step(TickInput { paddle_x: game.paddle_pos.x, fire: false });
+        }
+    }
+
+    #[test]
+    fn destroyed_bricks_sometimes_drop_capsules() {
+        let mut game = game(&["YYYYYYYYYYYYYY"]);
+        for _ in 0..13 {
+            hit(&mut game, 0);
+        }
+        assert!(!game.capsules.is_empty() && game.capsules.len() < 13, "{} capsules", game.capsules.len());
+        // each one starts in the middle of the brick it came from
+        for capsule in &game.capsules {
+            let column = (capsule.pos.x + CAPSULE_SIZE.x / 2.0) / (BRICK_SIZE.x + BRICK_GAP);
+            assert!((column.fract() - BRICK_SIZE.x / 2.0 / (BRICK_SIZE.x + BRICK_GAP)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn timed_effects_run_out() {
+        let mut game = game(&["Y"]);
+        park_ball(&mut game);
+        let speed = game.ball_speed();
+
+        catch(&mut game, PowerUp::Expand);
+        catch(&mut game, PowerUp::Slow);
+        assert_eq!(game.paddle_width(), PADDLE_SIZE.x * EXPAND_FACTOR);
+        assert!((game.balls[0].vel.length() - speed * SLOW_FACTOR).abs() < 1e-5);
+
+        wait(&mut game, PowerUp::Slow.duration().unwrap());
+        assert!(game.effects.active.is_empty());
+        assert_eq!(game.paddle_width(), PADDLE_SIZE.x);
+        assert!((game.balls[0].vel.length() - speed).abs() < 1e-5);
+    }
+
+    #[test]
+    fn one_off_power_ups_take_effect_when_caught() {
+        let mut game = game(&["Y"]);
+        park_ball(&mut game);
+        catch(&mut game, PowerUp::ExtraLife);
+        assert_eq!(game.balls_rem, 4);
+        catch(&mut game, PowerUp::MultiBall);
+        assert_eq!(game.balls.len(), 3);
+        assert!(game.effects.active.is_empty());
+    }
+
+    #[test]
+    fn lasers_break_bricks() {
+        let mut game = game(&["YYYYYYYYYYYYYY"]);
+        park_ball(&mut game);
+        catch(&mut game, PowerUp::Laser);
+        game.step(TickInput { paddle_x: game.paddle_pos.x, fire: true });
+        assert_eq!(game.lasers.len(), 2);
+        wait(&mut game, TICK_RATE);
+        assert!(game.lasers.is_empty());
+        assert_eq!(game.bricks.len(), 12);
+    }
+
+    #[test]
+    fn missed_capsules_and_effects_go_with_the_ball() {
+        let mut game = game(&["Y"]);
+        park_ball(&mut game);
+        catch(&mut game, PowerUp::Expand);
+        // a capsule at the far end from the paddle falls past it
+        let x = if game.paddle_pos.x > game.size.x / 2.0 { 0.0 } else { game.size.x - CAPSULE_SIZE.x };
+        game.capsules.push(Capsule { pos: vec2(x, game.paddle_pos.y - 50.0), kind: PowerUp::ExtraLife });
+        wait(&mut game, TICK_RATE);
+        assert!(game.capsules.is_empty());
+        assert_eq!(game.balls_rem, 3);
+
+        lose_ball(&mut game);
+        assert!(game.effects.active.is_empty());
+        assert_eq!(game.paddle_width(), PADDLE_SIZE.x);
+    }
+
+    /// A started game for two on a level with these layout rows.
+    fn two_players(layout: &[&str], lives: u8) -> Breakout {
+        let mut game = game(layout).with_tuning(Tuning { lives, players: 2, ..Tuning::default() });
+        game.start();
+        game
+    }
+
+    #[test]
+    fn losing_a_ball_passes_the_turn() {
+        let mut game = two_players(&["YYY"], 3);
+        hit(&mut game, 0);
+        lose_ball(&mut game);
+        assert_eq!(game.player, 1);
+        // each player has their own wall, score and balls
+        assert_eq!((game.bricks.len(), game.score, game.balls_rem), (3, 0, 3));
+        assert_eq!((game.player_score(0), game.player_balls(0)), (1, 2));
+
+        hit(&mut game, 0);
+        hit(&mut game, 0);
+        lose_ball(&mut game);
+        assert_eq!(game.player, 0);
+        assert_eq!((game.bricks.len(), game.score, game.balls_rem), (2, 1, 2));
+        assert_eq!((game.player_score(1), game.player_balls(1)), (2, 2));
+    }
+
+    #[test]
+    fn a_player_out_of_balls_is_skipped() {
+        let mut game = two_players(&["YYY"], 1);
+        hit(&mut game, 0);
+        lose_ball(&mut game);
+        assert_eq!((game.player, game.player_balls(0)), (1, 0));
+
+        park_ball(&mut game);
+        catch(&mut game, PowerUp::ExtraLife);
+        hit(&mut game, 0);
+        hit(&mut game, 0);
+        lose_ball(&mut game);
+        assert_eq!((game.player, game.balls_rem), (1, 1));
+        assert!(game.game_state == GameState::Playing);
+
+        lose_ball(&mut game);
+        assert!(game.game_state == GameState::GameOver);
+        assert_eq!((game.player_score(0), game.player_score(1)), (1, 2));
+        assert_eq!(game.winner(), Some(1));
+    }
+
+    #[test]
+    fn clearing_a_wall_hands_over_to_the_other_player() {
+        let mut game = two_players(&["Y"], 3);
+        hit(&mut game, 0);
+        assert_eq!(game.player, 1);
+        assert!(game.game_state == GameState::Playing);
+        assert_eq!(game.bricks.len(), 1);
+
+        hit(&mut game, 0);
+        assert!(game.game_state == GameState::Win);
+        assert_eq!(game.winner(), None);
+    }
+}
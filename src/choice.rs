@@ -0,0 +1,45 @@
+/// A setting picked from a fixed list, like the ruleset or the computer's skill level, with a
+/// name to show in menus and an id for files and the command line.
+pub trait Choice: Copy + PartialEq + 'static {
+    /// Every value in menu order, with its id and its name. Ids are saved in files, so they
+    /// don't change.
+    const CHOICES: &'static [(Self, &'static str, &'static str)];
+    /// What a value is called in error messages, e.g. "ruleset".
+    const KIND: &'static str;
+
+    fn all() -> impl Iterator<Item = Self> {
+        Self::CHOICES.iter().map(|&(value, _, _)| value)
+    }
+
+    fn id(&self) -> &'static str {
+        Self::CHOICES[self.index()].1
+    }
+
+    fn name(&self) -> &'static str {
+        Self::CHOICES[self.index()].2
+    }
+
+    fn from_id(id: &str) -> Option<Self> {
+        Self::all().find(|value| value.id() == id)
+    }
+
+    /// Like `from_id`, but with an error listing the ids there are.
+    fn parse(id: &str) -> Result<Self, String> {
+        Self::from_id(id).ok_or_else(|| {
+            let ids: Vec<&str> = Self::all().map(|value| value.id()).collect();
+            format!("`{}` is not a {}, expected one of: {}", id, Self::KIND, ids.join(", "))
+        })
+    }
+
+    /// Position in `CHOICES`.
+    fn index(&self) -> usize {
+        Self::all().position(|value| value == *self).expect("every value is listed")
+    }
+
+    /// The value `step` places along `CHOICES`, wrapping around, for changing a setting in a
+    /// menu.
+    fn cycle(&self, step: isize) -> Self {
+        let len = Self::CHOICES.len() as isize;
+        Self::CHOICES[(self.index() as isize + step).rem_euclid(len) as usize].0
+    }
+}
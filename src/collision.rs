@@ -0,0 +1,188 @@
+use macroquad::math::Vec2;
+
+// contacts closer together in time than this are treated as simultaneous
+const TIME_EPSILON: f32 = 1e-5;
+
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    pub fn from_vec(pos: Vec2, size: Vec2) -> Self {
+        Self {
+            x: pos.x,
+            y: pos.y,
+            width: size.x,
+            height: size.y,
+        }
+    }
+
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width
+            && self.x + self.width > other.x
+            && self.y < other.y + other.height
+            && self.y + self.height > other.y
+    }
+}
+
+/// The first contact of a moving box with a stationary one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// Time of impact as a fraction of the movement, from 0 to 1.
+    pub time: f32,
+    /// Surface normal of the target at the contact. Both components are set for a corner hit.
+    pub normal: Vec2,
+}
+
+impl Hit {
+    /// Whether two hits happen at the same moment and should be resolved together.
+    pub fn simultaneous(&self, other: &Hit) -> bool {
+        (self.time - other.time).abs() <= TIME_EPSILON
+    }
+
+    /// Combines the normals of two simultaneous hits, so that e.g. a ball entering the gap
+    /// between two bricks at a corner reflects on both axes.
+    pub fn merge(&mut self, other: &Hit) {
+        if self.normal.x == 0.0 {
+            self.normal.x = other.normal.x;
+        }
+        if self.normal.y == 0.0 {
+            self.normal.y = other.normal.y;
+        }
+    }
+
+    /// Reflects a velocity off the contact surface, keeping its magnitude.
+    pub fn reflect(&self, vel: Vec2) -> Vec2 {
+        let mut vel = vel;
+        if self.normal.x != 0.0 {
+            vel.x = vel.x.abs() * self.normal.x.signum();
+        }
+        if self.normal.y != 0.0 {
+            vel.y = vel.y.abs() * self.normal.y.signum();
+        }
+        vel
+    }
+}
+
+/// Sweeps `moving` along `delta` and finds when it first touches `target`, if it does so during
+/// the movement. Boxes that already overlap at the start are not reported.
+pub fn sweep(moving: &Rect, delta: Vec2, target: &Rect) -> Option<Hit> {
+    // grow the target by the moving box so the sweep becomes a ray cast from its corner
+    let (entry_x, exit_x) = slab(moving.x, delta.x, target.x - moving.width, target.x + target.width)?;
+    let (entry_y, exit_y) = slab(moving.y, delta.y, target.y - moving.height, target.y + target.height)?;
+
+    let entry = f32::max(entry_x, entry_y);
+    let exit = f32::min(exit_x, exit_y);
+    if entry >= exit || !(0.0..=1.0).contains(&entry) {
+        return None;
+    }
+
+    let mut normal = Vec2::ZERO;
+    if entry_x >= entry_y - TIME_EPSILON {
+        normal.x = -delta.x.signum();
+    }
+    if entry_y >= entry_x - TIME_EPSILON {
+        normal.y = -delta.y.signum();
+    }
+    Some(Hit { time: entry, normal })
+}
+
+/// Entry and exit times of a point moving along one axis through the interval `min..max`.
+fn slab(origin: f32, delta: f32, min: f32, max: f32) -> Option<(f32, f32)> {
+    if delta == 0.0 {
+        if origin > min && origin < max {
+            Some((f32::NEG_INFINITY, f32::INFINITY))
+        } else {
+            None
+        }
+    } else {
+        let t1 = (min - origin) / delta;
+        let t2 = (max - origin) / delta;
+        Some((f32::min(t1, t2), f32::max(t1, t2)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use macroquad::math::vec2;
+
+    fn rect(x: f32, y: f32, width: f32, height: f32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    #[test]
+    fn sweep_finds_the_time_and_side_of_contact() {
+        let ball = rect(0.0, 0.0, 10.0, 10.0);
+        let wall = rect(30.0, -50.0, 10.0, 100.0);
+        let hit = sweep(&ball, vec2(40.0, 0.0), &wall).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, vec2(-1.0, 0.0));
+
+        let floor = rect(-50.0, 20.0, 100.0, 10.0);
+        let hit = sweep(&ball, vec2(0.0, 20.0), &floor).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, vec2(0.0, -1.0));
+    }
+
+    #[test]
+    fn sweep_catches_a_box_moving_too_fast_to_overlap() {
+        // at the end of the step the ball is past the brick and never overlapped it
+        let ball = rect(0.0, 0.0, 4.0, 4.0);
+        let brick = rect(0.0, 50.0, 4.0, 2.0);
+        let delta = vec2(0.0, 100.0);
+        let end = rect(0.0, 100.0, 4.0, 4.0);
+        assert!(!end.intersects(&brick));
+        let hit = sweep(&ball, delta, &brick).unwrap();
+        assert_eq!(hit.time, 0.46);
+        assert_eq!(hit.normal, vec2(0.0, -1.0));
+    }
+
+    #[test]
+    fn sweep_misses() {
+        let ball = rect(0.0, 0.0, 10.0, 10.0);
+        // stops short
+        assert_eq!(sweep(&ball, vec2(10.0, 0.0), &rect(30.0, 0.0, 10.0, 10.0)), None);
+        // moving away
+        assert_eq!(sweep(&ball, vec2(-10.0, 0.0), &rect(30.0, 0.0, 10.0, 10.0)), None);
+        // passes alongside, touching edges don't count
+        assert_eq!(sweep(&ball, vec2(100.0, 0.0), &rect(30.0, 10.0, 10.0, 10.0)), None);
+        // already overlapping
+        assert_eq!(sweep(&ball, vec2(10.0, 0.0), &rect(5.0, 5.0, 10.0, 10.0)), None);
+    }
+
+    #[test]
+    fn sweep_reports_both_normals_on_a_corner() {
+        let ball = rect(0.0, 0.0, 10.0, 10.0);
+        let brick = rect(20.0, 20.0, 10.0, 10.0);
+        let hit = sweep(&ball, vec2(20.0, 20.0), &brick).unwrap();
+        assert_eq!(hit.time, 0.5);
+        assert_eq!(hit.normal, vec2(-1.0, -1.0));
+    }
+
+    #[test]
+    fn simultaneous_hits_merge_and_reflect_on_both_axes() {
+        // the ball goes into the gap between a brick above and one to the right
+        let ball = rect(0.0, 20.0, 10.0, 10.0);
+        let delta = vec2(10.0, -10.0);
+        let above = sweep(&ball, delta, &rect(-20.0, 5.0, 30.0, 10.0)).unwrap();
+        let right = sweep(&ball, delta, &rect(15.0, 10.0, 10.0, 30.0)).unwrap();
+        assert!(above.simultaneous(&right));
+
+        let mut hit = above;
+        hit.merge(&right);
+        assert_eq!(hit.normal, vec2(-1.0, 1.0));
+        assert_eq!(hit.reflect(vec2(3.0, -4.0)), vec2(-3.0, 4.0));
+    }
+
+    #[test]
+    fn reflect_keeps_speed_and_only_turns_into_the_normal() {
+        let hit = Hit { time: 0.0, normal: vec2(0.0, -1.0) };
+        assert_eq!(hit.reflect(vec2(3.0, 4.0)), vec2(3.0, -4.0));
+        // already moving away from the surface, e.g. after another hit this tick
+        assert_eq!(hit.reflect(vec2(3.0, -4.0)), vec2(3.0, -4.0));
+    }
+}
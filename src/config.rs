@@ -0,0 +1,517 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use toml::{Table, Value};
+
+use breakout::ai::{Skill, Strategy};
+use breakout::breakout::{MAX_ANGLE, MAX_PLAYERS, Tuning};
+use breakout::choice::Choice;
+use breakout::paths::config_dir;
+use breakout::rules::Ruleset;
+
+use crate::controls::{Action, Bindings, Button, key_from_name};
+use crate::screen::{VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+const FILE_NAME: &str = "config.toml";
+const MAX_LIVES: u8 = 9;
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+const MIN_WINDOW: u32 = 320;
+
+pub const USAGE: &str = "usage: breakout [options]
+       breakout verify [--standard] <replay>
+       breakout export-sounds <dir>
+       breakout thumbnail <replay> <png>
+       breakout render <replay> -o <out.gif or directory> [--fps <n>] [--scale <x>]
+
+options:
+  --config <file>       read settings from this file instead of the default config.toml
+  --fullscreen          run fullscreen
+  --windowed            run in a window
+  --width <pixels>      window width
+  --height <pixels>     window height
+  --integer-scaling     only scale the picture by whole numbers
+  --lives <n>           balls to start with, 1 to 9
+  --ball-speed <x>      ball speed multiplier, 0.25 to 4
+  --min-angle <deg>     bounce angle off the middle of the paddle, 0 to 80
+  --max-angle <deg>     bounce angle off the ends of the paddle, 0 to 80
+  --players <n>         1, or 2 to take turns
+  --ruleset <id>        single-wall or arcade
+  --level <file>        play a custom level
+  --seed <n>            use the same seed for every game
+  --autoplay <skill>    let the computer play: easy, normal, hard or perfect
+  --strategy <id>       how the computer aims: safe, tunnel or scatter
+  --opponent <skill>    let the computer be player 2 in games for two
+  --volume <x>          master volume, 0 to 1
+  --replay <file>       watch a recorded game
+  --help                show this message";
+
+/// Everything that can be set from the config file or the command line.
+#[derive(Clone, Debug)]
+pub struct Config {
+    pub fullscreen: bool,
+    pub width: u32,
+    pub height: u32,
+    pub integer_scaling: bool,
+    pub tuning: Tuning,
+    pub ruleset: Ruleset,
+    pub level: Option<PathBuf>,
+    /// A seed for every game instead of a random one.
+    pub seed: Option<u64>,
+    /// Has the computer play the games that are started, at this skill.
+    pub autoplay: Option<Skill>,
+    pub strategy: Strategy,
+    /// Has the computer take player 2's turns in games for two, at this skill.
+    pub opponent: Option<Skill>,
+    pub volume: f32,
+    pub muted: bool,
+    pub bindings: Bindings,
+    /// A recorded game to watch instead of playing.
+    pub replay: Option<PathBuf>,
+    /// The config file in use, where the options menu saves to. It needn't exist yet.
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            fullscreen: true,
+            width: VIRTUAL_WIDTH as u32,
+            height: VIRTUAL_HEIGHT as u32,
+            integer_scaling: false,
+            tuning: Tuning::default(),
+            ruleset: Ruleset::default(),
+            level: None,
+            seed: None,
+            autoplay: None,
+            strategy: Strategy::default(),
+            opponent: None,
+            volume: 1.0,
+            muted: false,
+            bindings: Bindings::default(),
+            replay: None,
+            path: None,
+        }
+    }
+}
+
+impl Config {
+    /// The config file given with `--config`, or the default one if it exists, with the rest
+    /// of the command line applied on top. `Ok(None)` means `--help` was asked for.
+    pub fn from_args(args: &[String]) -> Result<Option<Config>, String> {
+        let mut config = Config::default();
+        let explicit = flag_value(args, "--config")?;
+        let path = explicit.clone().map(PathBuf::from).or_else(|| config_dir().map(|dir| dir.join(FILE_NAME)));
+        if let Some(path) = &path {
+            // a missing default file just means nothing was configured
+            if explicit.is_some() || path.exists() {
+                config.load(path)?;
+            }
+        }
+        config.path = path;
+        if !config.apply_args(args)? {
+            return Ok(None);
+        }
+        let deflection = config.tuning.deflection;
+        if deflection.min_angle > deflection.max_angle {
+            return Err(format!(
+                "the paddle's min angle ({}) is more than its max angle ({})",
+                deflection.min_angle, deflection.max_angle
+            ));
+        }
+        Ok(Some(config))
+    }
+
+    /// Overrides settings from a config file.
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        let table: Table = text.parse().map_err(|err: toml::de::Error| {
+            let line = err.span().map_or(1, |span| text[..span.start].matches('\n').count() + 1);
+            format!("{}: line {}: {}", path.display(), line, err.message().trim_end().replace('\n', ", "))
+        })?;
+        self.apply_table(&table, path).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    fn apply_table(&mut self, table: &Table, path: &Path) -> Result<(), String> {
+        for (name, value) in table {
+            let section = value.as_table().ok_or_else(|| format!("`{}` should be a [section]", name))?;
+            let section = Section { name, table: section };
+            match name.as_str() {
+                "window" => {
+                    section.known(&["fullscreen", "width", "height", "integer_scaling"])?;
+                    section.set_bool("fullscreen", &mut self.fullscreen)?;
+                    section.set_number("width", &mut self.width, MIN_WINDOW..=u16::MAX as u32)?;
+                    section.set_number("height", &mut self.height, MIN_WINDOW..=u16::MAX as u32)?;
+                    section.set_bool("integer_scaling", &mut self.integer_scaling)?;
+                }
+                "game" => {
+                    section.known(&["lives", "ball_speed", "min_angle", "max_angle", "players", "ruleset", "level", "seed", "autoplay", "strategy", "opponent"])?;
+                    section.set_number("lives", &mut self.tuning.lives, 1..=MAX_LIVES)?;
+                    section.set_float("ball_speed", &mut self.tuning.speed, MIN_SPEED..=MAX_SPEED)?;
+                    section.set_float("min_angle", &mut self.tuning.deflection.min_angle, 0.0..=MAX_ANGLE)?;
+                    section.set_float("max_angle", &mut self.tuning.deflection.max_angle, 0.0..=MAX_ANGLE)?;
+                    section.set_number("players", &mut self.tuning.players, 1..=MAX_PLAYERS)?;
+                    if let Some(id) = section.string("ruleset")? {
+                        self.ruleset = Ruleset::parse(id).map_err(|err| section.error("ruleset", &err))?;
+                    }
+                    if let Some(level) = section.string("level")? {
+                        // relative to the config file, not wherever the game was started from
+                        self.level = Some(path.parent().unwrap_or(Path::new("")).join(level));
+                    }
+                    if let Some(seed) = section.get("seed") {
+                        let seed = seed.as_integer().ok_or_else(|| section.error("seed", "should be a whole number"))?;
+                        self.seed = Some(seed as u64);
+                    }
+                    if let Some(id) = section.string("autoplay")? {
+                        self.autoplay = Some(Skill::parse(id).map_err(|err| section.error("autoplay", &err))?);
+                    }
+                    if let Some(id) = section.string("strategy")? {
+                        self.strategy = Strategy::parse(id).map_err(|err| section.error("strategy", &err))?;
+                    }
+                    if let Some(id) = section.string("opponent")? {
+                        self.opponent = Some(Skill::parse(id).map_err(|err| section.error("opponent", &err))?);
+                    }
+                }
+                "audio" => {
+                    section.known(&["volume", "muted"])?;
+                    section.set_float("volume", &mut self.volume, 0.0..=1.0)?;
+                    section.set_bool("muted", &mut self.muted)?;
+                }
+                "keys" | "buttons" => {
+                    let names: Vec<&str> = Action::ALL.iter().map(Action::name).collect();
+                    section.known(&names)?;
+                    for action in Action::ALL {
+                        let Some(value) = section.get(action.name()) else {
+                            continue;
+                        };
+                        let error = |err: String| section.error(action.name(), &err);
+                        if name == "keys" {
+                            self.bindings.bind_keys(action, &parse_names(value, "key", key_from_name).map_err(error)?);
+                        } else {
+                            self.bindings.bind_buttons(action, &parse_names(value, "button", Button::from_name).map_err(error)?);
+                        }
+                    }
+                }
+                _ => return Err(format!("unknown section [{}], expected [window], [game], [audio], [keys] or [buttons]", name)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the settings the options menu can change into the config file, keeping whatever
+    /// else the file sets.
+    pub fn save_options(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut table = match fs::read_to_string(path) {
+            Ok(text) => text.parse().map_err(|err: toml::de::Error| format!("{}: {}", path.display(), err.message()))?,
+            Err(_) => Table::new(),
+        };
+        let window = section(&mut table, "window");
+        window.insert("fullscreen".into(), Value::Boolean(self.fullscreen));
+        window.insert("integer_scaling".into(), Value::Boolean(self.integer_scaling));
+        let audio = section(&mut table, "audio");
+        // rounded so that steps of 0.1 don't come out as 0.30000001
+        audio.insert("volume".into(), Value::Float((self.volume as f64 * 100.0).round() / 100.0));
+        audio.insert("muted".into(), Value::Boolean(self.muted));
+        let keys = section(&mut table, "keys");
+        for action in Action::ALL {
+            let names = self.bindings.keys(action).map(|key| Value::String(format!("{:?}", key))).collect();
+            keys.insert(action.name().into(), Value::Array(names));
+        }
+        let buttons = section(&mut table, "buttons");
+        for action in Action::ALL {
+            let names = self.bindings.buttons(action).map(|button| Value::String(format!("{:?}", button))).collect();
+            buttons.insert(action.name().into(), Value::Array(names));
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| format!("{}: {}", dir.display(), err))?;
+        }
+        fs::write(path, table.to_string()).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    /// Overrides settings from command-line flags. Returns false if `--help` was given.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<bool, String> {
+        let mut args = args.iter();
+        while let Some(flag) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", flag));
+            match flag.as_str() {
+                "--help" | "-h" => return Ok(false),
+                "--config" => {
+                    value()?;
+                }
+                "--fullscreen" => self.fullscreen = true,
+                "--windowed" => self.fullscreen = false,
+                "--integer-scaling" => self.integer_scaling = true,
+                "--width" => self.width = parse_flag(flag, value()?, MIN_WINDOW..=u16::MAX as u32)?,
+                "--height" => self.height = parse_flag(flag, value()?, MIN_WINDOW..=u16::MAX as u32)?,
+                "--lives" => self.tuning.lives = parse_flag(flag, value()?, 1..=MAX_LIVES)?,
+                "--ball-speed" => self.tuning.speed = parse_flag(flag, value()?, MIN_SPEED..=MAX_SPEED)?,
+                "--min-angle" => self.tuning.deflection.min_angle = parse_flag(flag, value()?, 0.0..=MAX_ANGLE)?,
+                "--max-angle" => self.tuning.deflection.max_angle = parse_flag(flag, value()?, 0.0..=MAX_ANGLE)?,
+                "--players" => self.tuning.players = parse_flag(flag, value()?, 1..=MAX_PLAYERS)?,
+                "--volume" => self.volume = parse_flag(flag, value()?, 0.0..=1.0)?,
+                "--seed" => self.seed = Some(parse_flag(flag, value()?, 0..=u64::MAX)?),
+                "--ruleset" => self.ruleset = Ruleset::parse(value()?).map_err(|err| format!("{}: {}", flag, err))?,
+                "--autoplay" => self.autoplay = Some(Skill::parse(value()?).map_err(|err| format!("{}: {}", flag, err))?),
+                "--strategy" => self.strategy = Strategy::parse(value()?).map_err(|err| format!("{}: {}", flag, err))?,
+                "--opponent" => self.opponent = Some(Skill::parse(value()?).map_err(|err| format!("{}: {}", flag, err))?),
+                "--level" => self.level = Some(PathBuf::from(value()?)),
+                "--replay" => self.replay = Some(PathBuf::from(value()?)),
+                _ => return Err(format!("unknown option `{}`, see --help", flag)),
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// One `[section]` of the config file.
+struct Section<'a> {
+    name: &'a str,
+    table: &'a Table,
+}
+
+impl Section<'_> {
+    fn error(&self, key: &str, message: &str) -> String {
+        format!("[{}] {} {}", self.name, key, message)
+    }
+
+    /// Rejects keys that aren't in `names`, which are most likely typos.
+    fn known(&self, names: &[&str]) -> Result<(), String> {
+        match self.table.keys().find(|key| !names.contains(&key.as_str())) {
+            Some(key) => Err(format!("unknown setting `{}` in [{}], expected one of: {}", key, self.name, names.join(", "))),
+            None => Ok(()),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.table.get(key)
+    }
+
+    fn set_bool(&self, key: &str, out: &mut bool) -> Result<(), String> {
+        if let Some(value) = self.get(key) {
+            *out = value.as_bool().ok_or_else(|| self.error(key, "should be true or false"))?;
+        }
+        Ok(())
+    }
+
+    fn set_number<T>(&self, key: &str, out: &mut T, range: std::ops::RangeInclusive<T>) -> Result<(), String>
+    where
+        T: TryFrom<i64> + PartialOrd + std::fmt::Display,
+    {
+        if let Some(value) = self.get(key) {
+            let message = format!("should be a whole number from {} to {}", range.start(), range.end());
+            let number = value.as_integer().and_then(|number| T::try_from(number).ok());
+            *out = number.filter(|number| range.contains(number)).ok_or_else(|| self.error(key, &message))?;
+        }
+        Ok(())
+    }
+
+    fn set_float(&self, key: &str, out: &mut f32, range: std::ops::RangeInclusive<f32>) -> Result<(), String> {
+        if let Some(value) = self.get(key) {
+            let message = format!("should be a number from {} to {}", range.start(), range.end());
+            // whole numbers are written without a point, so accept those too
+            let number = value.as_float().or_else(|| value.as_integer().map(|number| number as f64));
+            *out = number.map(|number| number as f32).filter(|number| range.contains(number)).ok_or_else(|| self.error(key, &message))?;
+        }
+        Ok(())
+    }
+
+    fn string(&self, key: &str) -> Result<Option<&str>, String> {
+        self.get(key).map(|value| value.as_str().ok_or_else(|| self.error(key, "should be a \"string\""))).transpose()
+    }
+}
+
+pub fn parse_flag<T>(flag: &str, value: &str, range: std::ops::RangeInclusive<T>) -> Result<T, String>
+where
+    T: FromStr + PartialOrd + std::fmt::Display,
+{
+    value
+        .parse()
+        .ok()
+        .filter(|number| range.contains(number))
+        .ok_or_else(|| format!("{}: expected a number from {} to {}, got `{}`", flag, range.start(), range.end(), value))
+}
+
+/// A key or button name or a list of them, looked up with `lookup`. `kind` is "key" or
+/// "button", for the errors.
+fn parse_names<T>(value: &Value, kind: &str, lookup: impl Fn(&str) -> Option<T>) -> Result<Vec<T>, String> {
+    let expected = || format!("should be a {} name or a list of them", kind);
+    let names = match value {
+        Value::String(name) => vec![name.as_str()],
+        Value::Array(names) => names.iter().map(|name| name.as_str().ok_or_else(expected)).collect::<Result<_, _>>()?,
+        _ => return Err(expected()),
+    };
+    names
+        .into_iter()
+        .map(|name| lookup(name).ok_or_else(|| format!("has unknown {} `{}`", kind, name)))
+        .collect()
+}
+
+/// The named section of a config file, replacing anything else by that name.
+fn section<'a>(table: &'a mut Table, name: &str) -> &'a mut Table {
+    let value = table.entry(name).or_insert_with(|| Value::Table(Table::new()));
+    if !value.is_table() {
+        *value = Value::Table(Table::new());
+    }
+    value.as_table_mut().expect("just made a table")
+}
+
+/// The value following `flag`, if it was given.
+fn flag_value(args: &[String], flag: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == flag) {
+        Some(i) => args.get(i + 1).cloned().map(Some).ok_or_else(|| format!("{} needs a value", flag)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use macroquad::input::KeyCode;
+
+    use super::*;
+
+    /// The config `text` makes with `args` applied on top. Errors have the file's path
+    /// replaced by `config.toml`.
+    fn config(text: &str, args: &[&str]) -> Result<Config, String> {
+        static FILES: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("breakout-config-test-{}-{}.toml", std::process::id(), FILES.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, text).unwrap();
+        let mut all = vec!["--config".to_owned(), path.display().to_string()];
+        all.extend(args.iter().map(|arg| arg.to_string()));
+        let config = Config::from_args(&all).map(|config| config.expect("not --help"));
+        fs::remove_file(&path).unwrap();
+        config.map_err(|err| err.replace(&path.display().to_string(), "config.toml"))
+    }
+
+    fn error(text: &str, args: &[&str]) -> String {
+        match config(text, args) {
+            Err(err) => err,
+            Ok(config) => panic!("accepted {:?}", config),
+        }
+    }
+
+    #[test]
+    fn reads_every_section() {
+        let config = config(
+            r#"
+                [window]
+                fullscreen = false
+                width = 800
+                integer_scaling = true
+
+                [game]
+                ruleset = "arcade"
+                lives = 5
+                ball_speed = 1.5
+                min_angle = 10
+                max_angle = 70.5
+                players = 2
+                level = "levels/fortress.level"
+                seed = 42
+                autoplay = "hard"
+                strategy = "scatter"
+                opponent = "easy"
+
+                [audio]
+                volume = 0.5
+                muted = true
+
+                [keys]
+                left = ["J", "Left"]
+                launch = "Enter"
+
+                [buttons]
+                launch = ["South", "RightTrigger"]
+                pause = "start"
+            "#,
+            &[],
+        )
+        .unwrap();
+        assert_eq!((config.fullscreen, config.width, config.height, config.integer_scaling), (false, 800, VIRTUAL_HEIGHT as u32, true));
+        assert_eq!(config.ruleset, Ruleset::Arcade);
+        assert_eq!((config.tuning.lives, config.tuning.speed, config.tuning.players), (5, 1.5, 2));
+        assert_eq!((config.tuning.deflection.min_angle, config.tuning.deflection.max_angle), (10.0, 70.5));
+        // relative to the config file
+        assert_eq!(config.level, Some(std::env::temp_dir().join("levels/fortress.level")));
+        assert_eq!(config.seed, Some(42));
+        assert_eq!((config.autoplay, config.strategy, config.opponent), (Some(Skill::Hard), Strategy::Scatter, Some(Skill::Easy)));
+        assert_eq!((config.volume, config.muted), (0.5, true));
+        assert_eq!(config.bindings.keys(Action::Left).collect::<Vec<_>>(), [KeyCode::J, KeyCode::Left]);
+        assert_eq!(config.bindings.keys(Action::Launch).collect::<Vec<_>>(), [KeyCode::Enter]);
+        assert_eq!(config.bindings.keys(Action::Right).collect::<Vec<_>>(), Bindings::default().keys(Action::Right).collect::<Vec<_>>());
+        assert_eq!(config.bindings.buttons(Action::Launch).collect::<Vec<_>>(), [Button::South, Button::RightTrigger]);
+        assert_eq!(config.bindings.buttons(Action::Pause).collect::<Vec<_>>(), [Button::Start]);
+    }
+
+    #[test]
+    fn an_empty_file_changes_nothing() {
+        let config = config("", &[]).unwrap();
+        let default = Config::default();
+        assert_eq!((config.fullscreen, config.tuning, config.ruleset, config.volume), (default.fullscreen, default.tuning, default.ruleset, default.volume));
+    }
+
+    #[test]
+    fn rejects_settings_out_of_range() {
+        for (text, message) in [
+            ("[game]\nlives = 0", "[game] lives should be a whole number from 1 to 9"),
+            ("[game]\nlives = 10", "[game] lives should be a whole number from 1 to 9"),
+            ("[game]\nball_speed = 0.2", "[game] ball_speed should be a number from 0.25 to 4"),
+            ("[game]\nball_speed = 5", "[game] ball_speed should be a number from 0.25 to 4"),
+            ("[game]\nmax_angle = 85", "[game] max_angle should be a number from 0 to 80"),
+            ("[game]\nplayers = 3", "[game] players should be a whole number from 1 to 2"),
+            ("[window]\nwidth = 100", "[window] width should be a whole number from 320 to 65535"),
+            ("[audio]\nvolume = 1.5", "[audio] volume should be a number from 0 to 1"),
+            ("[audio]\nmuted = 1", "[audio] muted should be true or false"),
+            ("[game]\nruleset = 2", "[game] ruleset should be a \"string\""),
+        ] {
+            assert_eq!(error(text, &[]), format!("config.toml: {}", message), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_and_malformed_settings() {
+        assert_eq!(error("[game]\nlifes = 3", &[]), "config.toml: unknown setting `lifes` in [game], expected one of: lives, ball_speed, min_angle, max_angle, players, ruleset, level, seed, autoplay, strategy, opponent");
+        assert_eq!(error("[sound]\nvolume = 1", &[]), "config.toml: unknown section [sound], expected [window], [game], [audio], [keys] or [buttons]");
+        assert_eq!(error("[buttons]\nlaunch = \"A\"", &[]), "config.toml: [buttons] launch has unknown button `A`");
+        assert_eq!(error("[buttons]\nlaunch = 1", &[]), "config.toml: [buttons] launch should be a button name or a list of them");
+        assert_eq!(error("volume = 1", &[]), "config.toml: `volume` should be a [section]");
+        assert_eq!(error("[keys]\nleft = \"Nope\"", &[]), "config.toml: [keys] left has unknown key `Nope`");
+        assert!(error("[game]\n\nlives = ", &[]).starts_with("config.toml: line 3: "));
+    }
+
+    #[test]
+    fn flags_win_over_the_file() {
+        let config = config("[game]\nlives = 5\nball_speed = 2\nruleset = \"arcade\"", &["--lives", "2", "--ruleset", "single-wall"]).unwrap();
+        assert_eq!((config.tuning.lives, config.tuning.speed, config.ruleset), (2, 2.0, Ruleset::SingleWall));
+    }
+
+    #[test]
+    fn rejects_flags_out_of_range() {
+        for (args, message) in [
+            (["--lives", "0"], "--lives: expected a number from 1 to 9, got `0`"),
+            (["--ball-speed", "4.5"], "--ball-speed: expected a number from 0.25 to 4, got `4.5`"),
+            (["--players", "3"], "--players: expected a number from 1 to 2, got `3`"),
+            (["--width", "wide"], "--width: expected a number from 320 to 65535, got `wide`"),
+            (["--ruleset", "pinball"], "--ruleset: `pinball` is not a ruleset, expected one of: single-wall, arcade"),
+        ] {
+            assert_eq!(error("", &args), message);
+        }
+        assert_eq!(error("", &["--lives"]), "--lives needs a value");
+        assert_eq!(error("", &["--turbo"]), "unknown option `--turbo`, see --help");
+    }
+
+    #[test]
+    fn the_paddle_angles_are_checked_once_everything_is_applied() {
+        // the file's max angle is below the default min, but the flag fixes that
+        let config = config("[game]\nmax_angle = 10", &["--min-angle", "5"]).unwrap();
+        assert_eq!((config.tuning.deflection.min_angle, config.tuning.deflection.max_angle), (5.0, 10.0));
+        assert_eq!(error("[game]\nmax_angle = 10", &[]), "the paddle's min angle (15) is more than its max angle (10)");
+    }
+}
@@ -0,0 +1,431 @@
+use macroquad::input::{is_key_down, is_key_pressed, is_mouse_button_pressed, KeyCode, mouse_position, MouseButton};
+use macroquad::time::get_frame_time;
+
+#[cfg(feature = "gamepad")]
+use gilrs::{Axis, EventType, Gilrs};
+
+use breakout::breakout::{Input, PaddleInput};
+
+// keyboard paddle speed in pixels per second, ramping up while a key is held
+const KEY_START_SPEED: f32 = 300.0;
+const KEY_MAX_SPEED: f32 = 1200.0;
+const KEY_ACCELERATION: f32 = 2400.0;
+// analog stick paddle speed at full tilt, in pixels per second
+#[cfg(feature = "gamepad")]
+const STICK_SPEED: f32 = 1000.0;
+#[cfg(feature = "gamepad")]
+const STICK_DEAD_ZONE: f32 = 0.15;
+
+/// Something the player can bind a key or button to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Left,
+    Right,
+    /// Pauses or resumes the game.
+    Pause,
+    /// Starts the game, launches a caught ball or fires the lasers.
+    Launch,
+    /// Abandons the current game for a new one.
+    Restart,
+    /// Turns the sound off or back on.
+    Mute,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [Action::Left, Action::Right, Action::Pause, Action::Launch, Action::Restart, Action::Mute];
+
+    /// The action's name in the config file.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::Pause => "pause",
+            Action::Launch => "launch",
+            Action::Restart => "restart",
+            Action::Mute => "mute",
+        }
+    }
+
+    /// The action's name in the controls menu.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Pause => "Pause",
+            Action::Launch => "Launch",
+            Action::Restart => "Restart",
+            Action::Mute => "Mute",
+        }
+    }
+}
+
+/// A step through a menu. These are fixed so that the menus can't be locked out by a bad
+/// binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nav {
+    Up,
+    Down,
+    Left,
+    Right,
+    Select,
+    Back,
+}
+
+const NAV_KEYS: [(KeyCode, Nav); 13] = [
+    (KeyCode::Up, Nav::Up),
+    (KeyCode::W, Nav::Up),
+    (KeyCode::Down, Nav::Down),
+    (KeyCode::S, Nav::Down),
+    (KeyCode::Left, Nav::Left),
+    (KeyCode::A, Nav::Left),
+    (KeyCode::Right, Nav::Right),
+    (KeyCode::D, Nav::Right),
+    (KeyCode::Enter, Nav::Select),
+    (KeyCode::KpEnter, Nav::Select),
+    (KeyCode::Space, Nav::Select),
+    (KeyCode::Escape, Nav::Back),
+    (KeyCode::Backspace, Nav::Back),
+];
+
+const NAV_BUTTONS: [(Button, Nav); 7] = [
+    (Button::DPadUp, Nav::Up),
+    (Button::DPadDown, Nav::Down),
+    (Button::DPadLeft, Nav::Left),
+    (Button::DPadRight, Nav::Right),
+    (Button::South, Nav::Select),
+    (Button::East, Nav::Back),
+    (Button::Start, Nav::Back),
+];
+
+/// A gamepad button, named like gilrs names them. Bindings are kept as these rather than as
+/// gilrs buttons so that they can be read, saved and rebound in builds without gamepad support.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    South,
+    East,
+    North,
+    West,
+    LeftTrigger,
+    LeftTrigger2,
+    RightTrigger,
+    RightTrigger2,
+    Select,
+    Start,
+    Mode,
+    LeftThumb,
+    RightThumb,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+impl Button {
+    pub const ALL: [Button; 17] = [
+        Button::South,
+        Button::East,
+        Button::North,
+        Button::West,
+        Button::LeftTrigger,
+        Button::LeftTrigger2,
+        Button::RightTrigger,
+        Button::RightTrigger2,
+        Button::Select,
+        Button::Start,
+        Button::Mode,
+        Button::LeftThumb,
+        Button::RightThumb,
+        Button::DPadUp,
+        Button::DPadDown,
+        Button::DPadLeft,
+        Button::DPadRight,
+    ];
+
+    /// A button from its name in the config file, ignoring case.
+    pub fn from_name(name: &str) -> Option<Button> {
+        Button::ALL.into_iter().find(|button| format!("{:?}", button).eq_ignore_ascii_case(name))
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn from_gilrs(button: gilrs::Button) -> Option<Button> {
+        Button::ALL.into_iter().find(|&ours| format!("{:?}", ours) == format!("{:?}", button))
+    }
+
+    /// The menu step the button makes, if any.
+    pub fn nav(&self) -> Option<Nav> {
+        NAV_BUTTONS.iter().find(|(button, _)| button == self).map(|&(_, nav)| nav)
+    }
+}
+
+/// Which keys and gamepad buttons trigger each action. An action can have several.
+#[derive(Clone, Debug)]
+pub struct Bindings {
+    pub keys: Vec<(Action, KeyCode)>,
+    pub buttons: Vec<(Action, Button)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            keys: vec![
+                (Action::Left, KeyCode::Left),
+                (Action::Left, KeyCode::A),
+                (Action::Right, KeyCode::Right),
+                (Action::Right, KeyCode::D),
+                (Action::Pause, KeyCode::Escape),
+                (Action::Pause, KeyCode::P),
+                (Action::Launch, KeyCode::Space),
+                (Action::Restart, KeyCode::R),
+                (Action::Mute, KeyCode::M),
+            ],
+            buttons: vec![
+                (Action::Left, Button::DPadLeft),
+                (Action::Right, Button::DPadRight),
+                (Action::Pause, Button::Start),
+                (Action::Launch, Button::South),
+                (Action::Restart, Button::Select),
+            ],
+        }
+    }
+}
+
+impl Bindings {
+    /// Replaces the keys bound to an action.
+    pub fn bind_keys(&mut self, action: Action, keys: &[KeyCode]) {
+        self.keys.retain(|&(bound, _)| bound != action);
+        self.keys.extend(keys.iter().map(|&key| (action, key)));
+    }
+
+    /// Replaces the gamepad buttons bound to an action.
+    pub fn bind_buttons(&mut self, action: Action, buttons: &[Button]) {
+        self.buttons.retain(|&(bound, _)| bound != action);
+        self.buttons.extend(buttons.iter().map(|&button| (action, button)));
+    }
+
+    /// The keys bound to an action.
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = KeyCode> + '_ {
+        self.keys.iter().filter(move |&&(bound, _)| bound == action).map(|&(_, key)| key)
+    }
+
+    /// The gamepad buttons bound to an action.
+    pub fn buttons(&self, action: Action) -> impl Iterator<Item = Button> + '_ {
+        self.buttons.iter().filter(move |&&(bound, _)| bound == action).map(|&(_, button)| button)
+    }
+
+    /// Whether any of `buttons` triggers the action.
+    pub fn any_button(&self, action: Action, buttons: &[Button]) -> bool {
+        self.buttons(action).any(|button| buttons.contains(&button))
+    }
+}
+
+/// Turns mouse, keyboard and gamepad input into paddle movement and actions.
+pub struct Controls {
+    pub bindings: Bindings,
+    last_mouse_x: f32,
+    key_speed: f32,
+    #[cfg(feature = "gamepad")]
+    gamepad: Gamepad,
+}
+
+impl Controls {
+    pub fn new(bindings: Bindings) -> Self {
+        Self {
+            bindings,
+            last_mouse_x: mouse_position().0,
+            key_speed: KEY_START_SPEED,
+            #[cfg(feature = "gamepad")]
+            gamepad: Gamepad::new(),
+        }
+    }
+
+    /// Polls all input devices for this frame. Call once per frame, before `pressed`. `scale` is
+    /// window pixels per playfield unit, so the mouse covers the same ground at any window size.
+    pub fn input(&mut self, scale: f32) -> Input {
+        let dt = get_frame_time();
+        #[cfg(feature = "gamepad")]
+        self.gamepad.poll();
+
+        let mouse_x = mouse_position().0;
+        let mut delta = (mouse_x - self.last_mouse_x) / scale;
+        self.last_mouse_x = mouse_x;
+
+        let direction = self.held(Action::Right) as i8 - self.held(Action::Left) as i8;
+        if direction == 0 {
+            self.key_speed = KEY_START_SPEED;
+        } else {
+            delta += direction as f32 * self.key_speed * dt;
+            self.key_speed = f32::min(self.key_speed + KEY_ACCELERATION * dt, KEY_MAX_SPEED);
+        }
+
+        #[cfg(feature = "gamepad")]
+        {
+            delta += self.gamepad.stick_x() * STICK_SPEED * dt;
+        }
+
+        Input {
+            paddle: PaddleInput::Delta(delta),
+            fire: is_mouse_button_pressed(MouseButton::Left) || self.pressed(Action::Launch),
+            dt,
+        }
+    }
+
+    /// Whether an action was triggered this frame.
+    pub fn pressed(&self, action: Action) -> bool {
+        self.bindings.any_button(action, self.buttons_pressed()) || self.bindings.keys(action).any(is_key_pressed)
+    }
+
+    /// This frame's step through a menu, if any.
+    pub fn nav(&self) -> Option<Nav> {
+        if let Some(nav) = self.buttons_pressed().iter().find_map(Button::nav) {
+            return Some(nav);
+        }
+        NAV_KEYS.iter().find(|&&(key, _)| is_key_pressed(key)).map(|&(_, nav)| nav)
+    }
+
+    /// The gamepad buttons pressed this frame, for rebinding them.
+    pub fn buttons_pressed(&self) -> &[Button] {
+        #[cfg(feature = "gamepad")]
+        return &self.gamepad.pressed;
+        #[cfg(not(feature = "gamepad"))]
+        &[]
+    }
+
+    /// Whether an action is being held down.
+    fn held(&self, action: Action) -> bool {
+        #[cfg(feature = "gamepad")]
+        if self.bindings.any_button(action, &self.gamepad.held) {
+            return true;
+        }
+        self.bindings.keys(action).any(is_key_down)
+    }
+}
+
+/// Gamepad state gathered from gilrs events each frame.
+#[cfg(feature = "gamepad")]
+struct Gamepad {
+    gilrs: Option<Gilrs>,
+    pressed: Vec<Button>,
+    held: Vec<Button>,
+    stick_x: f32,
+}
+
+#[cfg(feature = "gamepad")]
+impl Gamepad {
+    fn new() -> Self {
+        // without gamepad support from the OS the game still runs on mouse and keyboard
+        let gilrs = Gilrs::new().map_err(|err| eprintln!("gamepads unavailable: {}", err)).ok();
+        Self {
+            gilrs,
+            pressed: Vec::new(),
+            held: Vec::new(),
+            stick_x: 0.0,
+        }
+    }
+
+    fn poll(&mut self) {
+        self.pressed.clear();
+        let Some(gilrs) = &mut self.gilrs else {
+            return;
+        };
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::ButtonPressed(button, _) => {
+                    if let Some(button) = Button::from_gilrs(button) {
+                        self.pressed.push(button);
+                        self.held.push(button);
+                    }
+                }
+                EventType::ButtonReleased(button, _) => {
+                    let button = Button::from_gilrs(button);
+                    self.held.retain(|&held| Some(held) != button);
+                }
+                EventType::AxisChanged(Axis::LeftStickX, value, _) => self.stick_x = value,
+                EventType::Disconnected => {
+                    self.held.clear();
+                    self.stick_x = 0.0;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn stick_x(&self) -> f32 {
+        if self.stick_x.abs() < STICK_DEAD_ZONE {
+            0.0
+        } else {
+            self.stick_x
+        }
+    }
+}
+
+/// A key from its name in the config file, which is its `KeyCode` variant, ignoring case.
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    use KeyCode::*;
+    const KEYS: &[KeyCode] = &[
+        A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+        Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+        Space, Enter, Escape, Tab, Backspace, Insert, Delete, Home, End, PageUp, PageDown,
+        Left, Right, Up, Down,
+        F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+        LeftShift, RightShift, LeftControl, RightControl, LeftAlt, RightAlt,
+        Minus, Equal, Comma, Period, Slash, Semicolon, Apostrophe, LeftBracket, RightBracket, Backslash, GraveAccent,
+        Kp0, Kp1, Kp2, Kp3, Kp4, Kp5, Kp6, Kp7, Kp8, Kp9, KpEnter, KpAdd, KpSubtract,
+    ];
+    KEYS.iter().copied().find(|key| format!("{:?}", key).eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_cover_the_keyboard_and_gamepad() {
+        let bindings = Bindings::default();
+        assert_eq!(bindings.keys(Action::Left).collect::<Vec<_>>(), [KeyCode::Left, KeyCode::A]);
+        assert_eq!(bindings.buttons(Action::Launch).collect::<Vec<_>>(), [Button::South]);
+        assert!(bindings.any_button(Action::Pause, &[Button::North, Button::Start]));
+        assert!(!bindings.any_button(Action::Pause, &[Button::South]));
+        assert_eq!(bindings.buttons(Action::Mute).count(), 0);
+    }
+
+    #[test]
+    fn rebinding_replaces_only_that_action_and_device() {
+        let mut bindings = Bindings::default();
+        bindings.bind_buttons(Action::Launch, &[Button::East, Button::RightTrigger]);
+        assert!(bindings.any_button(Action::Launch, &[Button::RightTrigger]));
+        assert!(!bindings.any_button(Action::Launch, &[Button::South]));
+        assert_eq!(bindings.keys(Action::Launch).collect::<Vec<_>>(), [KeyCode::Space]);
+        assert_eq!(bindings.buttons(Action::Pause).collect::<Vec<_>>(), [Button::Start]);
+
+        bindings.bind_keys(Action::Launch, &[KeyCode::Enter]);
+        assert_eq!(bindings.keys(Action::Launch).collect::<Vec<_>>(), [KeyCode::Enter]);
+        assert_eq!(bindings.buttons(Action::Launch).count(), 2);
+    }
+
+    #[test]
+    fn buttons_are_named_like_gilrs_names_them() {
+        for button in Button::ALL {
+            assert_eq!(Button::from_name(&format!("{:?}", button)), Some(button));
+        }
+        assert_eq!(Button::from_name("dpadleft"), Some(Button::DPadLeft));
+        assert_eq!(Button::from_name("A"), None);
+        assert_eq!(key_from_name("space"), Some(KeyCode::Space));
+        assert_eq!(key_from_name("South"), None);
+    }
+
+    #[test]
+    fn menu_buttons_are_fixed() {
+        assert_eq!(Button::DPadUp.nav(), Some(Nav::Up));
+        assert_eq!(Button::South.nav(), Some(Nav::Select));
+        assert_eq!(Button::Start.nav(), Some(Nav::Back));
+        assert_eq!(Button::North.nav(), None);
+    }
+
+    #[cfg(feature = "gamepad")]
+    #[test]
+    fn converts_gilrs_buttons() {
+        assert_eq!(Button::from_gilrs(gilrs::Button::DPadRight), Some(Button::DPadRight));
+        assert_eq!(Button::from_gilrs(gilrs::Button::LeftTrigger2), Some(Button::LeftTrigger2));
+        assert_eq!(Button::from_gilrs(gilrs::Button::Unknown), None);
+    }
+}
@@ -0,0 +1,327 @@
+use macroquad::math::{Vec2, vec2};
+
+use crate::breakout::{BALL_SIZE, BASE_SPEED, BRICK_COUNT, BRICK_GAP, BRICK_SIZE, Breakout, GAME_HEIGHT, GAME_WIDTH, GameState, PADDING, TICK, TICK_RATE, TickInput, Tuning};
+use crate::level::{Level, MAX_ROWS};
+use crate::raster::{Canvas, draw_playfield};
+use crate::rules::Ruleset;
+
+/// Paddle and ball values at the start of a feature vector, before the brick bitmap.
+pub const STATE_FEATURES: usize = 7;
+/// Length of a feature vector: the paddle and ball values, then one value for every cell a
+/// brick can be in.
+pub const FEATURE_LEN: usize = STATE_FEATURES + MAX_ROWS * BRICK_COUNT as usize;
+
+/// A move for one step, numbered like the Atari Breakout environments agents are usually
+/// trained on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Noop,
+    /// Launches a caught ball or fires the lasers.
+    Fire,
+    Right,
+    Left,
+}
+
+impl Action {
+    pub const ALL: [Action; 4] = [Action::Noop, Action::Fire, Action::Right, Action::Left];
+
+    /// The action with the given number, for agents that pick actions by index.
+    pub fn from_index(index: usize) -> Option<Action> {
+        Action::ALL.get(index).copied()
+    }
+}
+
+/// What an agent gets to see.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ObservationKind {
+    /// `FEATURE_LEN` numbers describing the paddle, the ball and the wall. See
+    /// `Env::features`.
+    Features,
+    /// The playfield drawn at this size as RGB bytes, three to a pixel, rows from the top.
+    Pixels { width: usize, height: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Observation {
+    Features(Vec<f32>),
+    Pixels(Vec<u8>),
+}
+
+/// How the environment is set up. The game settings are the same as for a person playing.
+#[derive(Clone, Debug)]
+pub struct EnvConfig {
+    pub ruleset: Ruleset,
+    pub level: Level,
+    pub tuning: Tuning,
+    /// Ticks each action is repeated for, at `TICK_RATE` ticks a second.
+    pub frame_skip: u32,
+    pub observation: ObservationKind,
+    /// How fast `Left` and `Right` move the paddle, in pixels per millisecond.
+    pub paddle_speed: f32,
+    /// Ends an episode after this many ticks, in case the agent learns to keep the ball in play
+    /// without breaking anything.
+    pub max_ticks: Option<u64>,
+}
+
+impl Default for EnvConfig {
+    fn default() -> Self {
+        Self {
+            ruleset: Ruleset::default(),
+            level: Level::default(),
+            tuning: Tuning::default(),
+            frame_skip: 4,
+            observation: ObservationKind::Features,
+            paddle_speed: 1.5,
+            // ten minutes of play
+            max_ticks: Some(TICK_RATE as u64 * 600),
+        }
+    }
+}
+
+/// Extra details about a step, not meant for the agent to learn from. In a game for two the
+/// score, balls and walls are those of the player who took the step.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Info {
+    pub score: u16,
+    pub balls_rem: u8,
+    /// Whether a ball was lost during the step.
+    pub ball_lost: bool,
+    pub walls_cleared: u8,
+    pub ticks: u64,
+    /// Whether the episode was cut short by `max_ticks` rather than won or lost.
+    pub truncated: bool,
+}
+
+/// The result of `Env::step`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Step {
+    pub observation: Observation,
+    /// Points scored during the step.
+    pub reward: f32,
+    pub done: bool,
+    pub info: Info,
+}
+
+/// The game as a reinforcement-learning environment, in the style of OpenAI Gym: `reset` starts
+/// an episode and `step` plays an action for `frame_skip` ticks. It runs without a window or a
+/// GPU, and episodes with the same seed and actions play out identically.
+pub struct Env {
+    pub config: EnvConfig,
+    game: Breakout,
+}
+
+impl Env {
+    pub fn new(config: EnvConfig) -> Self {
+        let game = Env::new_game(&config, 0);
+        Self { config, game }
+    }
+
+    fn new_game(config: &EnvConfig, seed: u64) -> Breakout {
+        let mut game = Breakout::new(vec2(GAME_WIDTH, GAME_HEIGHT), config.ruleset, config.level.clone(), seed)
+            .with_tuning(config.tuning);
+        game.start();
+        game
+    }
+
+    /// Starts a new episode and returns the first observation.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = Env::new_game(&self.config, seed);
+        self.observe()
+    }
+
+    /// Plays `action` for `frame_skip` ticks, or until the episode ends.
+    pub fn step(&mut self, action: Action) -> Step {
+        // in a game for two the turn can pass during the step, and it is scored for the player
+        // who took it
+        let player = self.game.player;
+        let score = self.game.player_score(player);
+        let balls_rem = self.game.player_balls(player);
+        let reach = self.config.paddle_speed * TICK * 1000.0;
+        for _ in 0..self.config.frame_skip.max(1) {
+            if self.game.game_state != GameState::Playing {
+                break;
+            }
+            let paddle_x = match action {
+                Action::Left => self.game.paddle_pos.x - reach,
+                Action::Right => self.game.paddle_pos.x + reach,
+                Action::Noop | Action::Fire => self.game.paddle_pos.x,
+            };
+            self.game.step(TickInput { paddle_x, fire: action == Action::Fire });
+        }
+        // nothing listens for them, and they would pile up over an episode
+        self.game.sounds.clear();
+
+        let truncated = self.config.max_ticks.is_some_and(|max| self.game.ticks >= max) && self.game.game_state == GameState::Playing;
+        Step {
+            observation: self.observe(),
+            reward: (self.game.player_score(player) - score) as f32,
+            done: self.game.game_state != GameState::Playing || truncated,
+            info: Info {
+                score: self.game.player_score(player),
+                balls_rem: self.game.player_balls(player),
+                ball_lost: self.game.player_balls(player) < balls_rem,
+                walls_cleared: match &self.game.waiting {
+                    Some(turn) if self.game.player != player => turn.game_count,
+                    _ => self.game.game_count,
+                },
+                ticks: self.game.ticks,
+                truncated,
+            },
+        }
+    }
+
+    /// The game being played, e.g. for drawing it.
+    pub fn game(&self) -> &Breakout {
+        &self.game
+    }
+
+    pub fn observe(&self) -> Observation {
+        match self.config.observation {
+            ObservationKind::Features => Observation::Features(self.features()),
+            ObservationKind::Pixels { width, height } => Observation::Pixels(self.pixels(width, height).rgb()),
+        }
+    }
+
+    /// The game as `FEATURE_LEN` numbers, with positions as fractions of the playfield:
+    ///
+    /// - the paddle's centre and width
+    /// - the first ball's centre, its velocity in multiples of the starting speed, and 1 if it
+    ///   is caught on the paddle or 0 if not
+    /// - the wall as `MAX_ROWS` rows of `BRICK_COUNT` cells from the top left, each the fraction
+    ///   of hits its brick has left, 1 for steel and 0 for no brick
+    pub fn features(&self) -> Vec<f32> {
+        let game = &self.game;
+        let size = game.size;
+        let mut features = vec![0.0; FEATURE_LEN];
+        let paddle = game.paddle_rect();
+        features[0] = (paddle.x + paddle.width / 2.0) / size.x;
+        features[1] = paddle.width / size.x;
+        if let Some(ball) = game.balls.first() {
+            let centre = (ball.pos + BALL_SIZE / 2.0) / size;
+            let vel = ball.vel / BASE_SPEED;
+            features[2..STATE_FEATURES].copy_from_slice(&[centre.x, centre.y, vel.x, vel.y, ball.caught.is_some() as u8 as f32]);
+        }
+
+        let pitch = BRICK_SIZE + Vec2::splat(BRICK_GAP);
+        for brick in &game.bricks {
+            let cell = ((brick.pos - vec2(0.0, PADDING)) / pitch).round();
+            if cell.x < 0.0 || cell.y < 0.0 || cell.x >= BRICK_COUNT as f32 || cell.y >= MAX_ROWS as f32 {
+                continue;
+            }
+            let health = if brick.breakable() { brick.hits as f32 / brick.max_hits as f32 } else { 1.0 };
+            features[STATE_FEATURES + cell.y as usize * BRICK_COUNT as usize + cell.x as usize] = health;
+        }
+        features
+    }
+
+    /// The playfield drawn at `width` by `height` pixels.
+    pub fn pixels(&self, width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        let scale = vec2(width as f32, height as f32) / self.game.size;
+        draw_playfield(&mut canvas, &self.game, Vec2::ZERO, scale, 1.0);
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Follows the ball with the paddle, which scores a few points before long.
+    fn follow(env: &Env) -> Action {
+        let game = env.game();
+        let paddle = game.paddle_rect();
+        let ball = game.balls[0].pos.x + BALL_SIZE.x / 2.0;
+        if ball < paddle.x + paddle.width / 3.0 {
+            Action::Left
+        } else if ball > paddle.x + paddle.width * 2.0 / 3.0 {
+            Action::Right
+        } else {
+            Action::Fire
+        }
+    }
+
+    /// Plays up to `steps` steps, returning every step.
+    fn play(env: &mut Env, seed: u64, steps: usize) -> Vec<Step> {
+        env.reset(seed);
+        let mut played = Vec::new();
+        while played.len() < steps {
+            let step = env.step(follow(env));
+            let done = step.done;
+            played.push(step);
+            if done {
+                break;
+            }
+        }
+        played
+    }
+
+    #[test]
+    fn features_cover_the_paddle_ball_and_every_cell() {
+        assert_eq!(FEATURE_LEN, 7 + 16 * 14);
+        let mut env = Env::new(EnvConfig::default());
+        let Observation::Features(features) = env.reset(1) else {
+            panic!("expected features");
+        };
+        assert_eq!(features.len(), FEATURE_LEN);
+        assert_eq!(features[0], 0.5);
+        // the classic wall fills the top eight rows
+        let cells = &features[STATE_FEATURES..];
+        assert!(cells[..8 * 14].iter().all(|&cell| cell == 1.0));
+        assert!(cells[8 * 14..].iter().all(|&cell| cell == 0.0));
+    }
+
+    #[test]
+    fn pixels_are_rgb() {
+        let mut env = Env::new(EnvConfig { observation: ObservationKind::Pixels { width: 84, height: 104 }, ..EnvConfig::default() });
+        let Observation::Pixels(pixels) = env.reset(1) else {
+            panic!("expected pixels");
+        };
+        assert_eq!(pixels.len(), 84 * 104 * 3);
+        assert!(pixels.iter().any(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn the_same_seed_and_actions_give_the_same_episode() {
+        let mut env = Env::new(EnvConfig::default());
+        let first = play(&mut env, 7, 3000);
+        let second = play(&mut env, 7, 3000);
+        assert!(first == second);
+        assert_eq!(Action::from_index(2), Some(Action::Right));
+        assert_eq!(Action::from_index(4), None);
+    }
+
+    #[test]
+    fn the_reward_is_the_points_scored() {
+        let mut env = Env::new(EnvConfig::default());
+        let steps = play(&mut env, 3, 3000);
+        let mut score = 0;
+        for step in &steps {
+            assert_eq!(step.reward, (step.info.score - score) as f32);
+            score = step.info.score;
+        }
+        assert!(score > 0);
+    }
+
+    #[test]
+    fn an_episode_ends_when_the_balls_run_out() {
+        let mut env = Env::new(EnvConfig { tuning: Tuning { lives: 1, ..Tuning::default() }, ..EnvConfig::default() });
+        env.reset(1);
+        // the ball is served down and to the right
+        let step = (0..1000).map(|_| env.step(Action::Left)).find(|step| step.done).expect("the ball was lost");
+        assert!(step.info.ball_lost);
+        assert_eq!((step.info.balls_rem, step.info.truncated), (0, false));
+    }
+
+    #[test]
+    fn max_ticks_cuts_an_episode_short() {
+        let mut env = Env::new(EnvConfig { frame_skip: 4, max_ticks: Some(40), ..EnvConfig::default() });
+        env.reset(1);
+        for _ in 0..9 {
+            assert!(!env.step(Action::Noop).done);
+        }
+        let step = env.step(Action::Noop);
+        assert!(step.done && step.info.truncated);
+        assert_eq!(step.info.ticks, 40);
+    }
+}
@@ -0,0 +1,264 @@
+use macroquad::color::{BLACK, Color, GRAY, RED, SKYBLUE, WHITE};
+use macroquad::input::{get_char_pressed, is_key_pressed, KeyCode};
+use macroquad::math::{Vec2, vec2};
+use macroquad::shapes::{draw_rectangle, draw_rectangle_lines};
+use macroquad::text::{draw_text, get_text_center, measure_text};
+use macroquad::time::get_time;
+use macroquad::window::clear_background;
+
+use breakout::breakout::{BALL_SIZE, BRICK_SIZE, BrickKind, Breakout, MAX_PLAYERS, TICK_RATE};
+use breakout::choice::Choice;
+use breakout::highscore::{Entry, NameEntry, TABLE_SIZE};
+use breakout::powerup::{CAPSULE_SIZE, LASER_SIZE};
+use breakout::rules::Ruleset;
+
+use crate::menu::{Item, MENU_TOP, item_y};
+use crate::screen::{VIRTUAL_HEIGHT, VIRTUAL_WIDTH};
+
+// seconds a notice stays up
+const NOTICE_TIME: f64 = 1.5;
+const HINT_SIZE: u16 = 32;
+// dims the game behind a menu
+const SHADE: Color = Color::new(0.0, 0.0, 0.0, 0.75);
+
+const CREDITS: [&str; 6] = [
+    "Atari Breakout recreated in Rust",
+    "",
+    "Originally created during CSCI 3430:",
+    "Principles of Programming Languages",
+    "at Saint Mary's University",
+    "Made with Macroquad",
+];
+
+/// Draws a `Breakout` simulation and its menus to the window.
+pub struct Frontend {
+    pub font_size: u16,
+    /// Initials being entered for a new high score.
+    pub name_entry: Option<NameEntry>,
+    /// Where the last entered score placed in its table, to highlight it.
+    pub last_place: Option<usize>,
+    /// A short message for the top of the screen and the time it disappears.
+    notice: Option<(String, f64)>,
+}
+
+impl Frontend {
+    pub fn new(font_size: u16) -> Self {
+        Self {
+            font_size,
+            name_entry: None,
+            last_place: None,
+            notice: None,
+        }
+    }
+
+    /// Briefly shows a message, such as a setting that was just changed.
+    pub fn show_notice(&mut self, text: String) {
+        self.notice = Some((text, get_time() + NOTICE_TIME));
+    }
+
+    /// Feeds this frame's key presses to the initials entry. Returns the initials once all
+    /// letters are entered.
+    pub fn poll_name_entry(&mut self) -> Option<String> {
+        let entry = self.name_entry.as_mut()?;
+        let mut done = false;
+        while let Some(c) = get_char_pressed() {
+            if c.is_ascii_alphabetic() {
+                done |= entry.type_letter(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Up) {
+            entry.cycle(1);
+        }
+        if is_key_pressed(KeyCode::Down) {
+            entry.cycle(-1);
+        }
+        if is_key_pressed(KeyCode::Left) || is_key_pressed(KeyCode::Backspace) {
+            entry.back();
+        }
+        if is_key_pressed(KeyCode::Right) || is_key_pressed(KeyCode::Enter) {
+            done |= entry.confirm();
+        }
+        if !done {
+            return None;
+        }
+        self.name_entry.take().map(|entry| entry.initials())
+    }
+
+    /// Draws the playfield and the score. Scenes draw their screens over it.
+    pub fn draw(&self, game: &Breakout) {
+        clear_background(BLACK);
+        let width = game.size.x;
+        let offset = (VIRTUAL_WIDTH - width) / 2.0;
+
+        // border
+        draw_rectangle_lines(offset - 8.0, 0.0, width + 16.0, VIRTUAL_HEIGHT, 16.0, WHITE);
+        // paddle
+        let paddle = game.paddle_rect();
+        draw_rectangle(offset + paddle.x, paddle.y, paddle.width, paddle.height, SKYBLUE);
+        // balls
+        for ball in &game.balls {
+            let pos = ball.render_pos(game.alpha());
+            draw_rectangle(offset + pos.x, pos.y, BALL_SIZE.x, BALL_SIZE.y, WHITE);
+        }
+
+        // bricks
+        for brick in &game.bricks {
+            draw_rectangle(offset + brick.pos.x, brick.pos.y, BRICK_SIZE.x, BRICK_SIZE.y, brick.color());
+            if brick.kind == BrickKind::Explosive {
+                draw_rectangle_lines(offset + brick.pos.x + 4.0, brick.pos.y + 4.0, BRICK_SIZE.x - 8.0, BRICK_SIZE.y - 8.0, 2.0, BLACK);
+            }
+        }
+
+        // power-ups
+        for capsule in &game.capsules {
+            let (x, y) = (offset + capsule.pos.x, capsule.pos.y);
+            draw_rectangle(x, y, CAPSULE_SIZE.x, CAPSULE_SIZE.y, capsule.kind.color());
+            draw_text(capsule.kind.letter(), x + CAPSULE_SIZE.x / 2.0 - 5.0, y + CAPSULE_SIZE.y - 1.0, 20.0, WHITE);
+        }
+        for laser in &game.lasers {
+            draw_rectangle(offset + laser.x, laser.y, LASER_SIZE.x, LASER_SIZE.y, RED);
+        }
+        self.draw_effects(game, offset);
+
+        // score and balls rem, or for two players both scores, whoever isn't playing dimmed, and
+        // the balls of whoever is between them
+        if game.waiting.is_none() {
+            self.label(&format!("{:03}", game.score), vec2(offset + 16.0, 32.0), WHITE);
+            self.label(&game.balls_rem.to_string(), vec2(offset + width - 100.0, 32.0), WHITE);
+        } else {
+            let color = |player: u8| if player == game.player { WHITE } else { GRAY };
+            self.label(&format!("{:03}", game.player_score(0)), vec2(offset + 16.0, 32.0), color(0));
+            let text = format!("{:03}", game.player_score(1));
+            let size = measure_text(&text, None, self.font_size, 1.0);
+            self.label(&text, vec2(offset + width - 16.0 - size.width, 32.0), color(1));
+            let text = game.balls_rem.to_string();
+            let size = measure_text(&text, None, self.font_size, 1.0);
+            self.label(&text, vec2(offset + (width - size.width) / 2.0, 32.0), WHITE);
+        }
+        if let Some((text, until)) = &self.notice {
+            if get_time() < *until {
+                let center = get_text_center(text, None, 32, 1.0, 0.0);
+                draw_text(text, VIRTUAL_WIDTH / 2.0 - center.x, 48.0, 32.0, WHITE);
+            }
+        }
+    }
+
+    /// Lists the active power-ups and their seconds left under the paddle.
+    fn draw_effects(&self, game: &Breakout, offset: f32) {
+        let y = game.paddle_pos.y + 64.0;
+        for (i, &(kind, ticks)) in game.effects.active.iter().enumerate() {
+            let x = offset + 16.0 + i as f32 * 96.0;
+            draw_rectangle(x, y, CAPSULE_SIZE.x, CAPSULE_SIZE.y, kind.color());
+            draw_text(kind.letter(), x + CAPSULE_SIZE.x / 2.0 - 5.0, y + CAPSULE_SIZE.y - 1.0, 20.0, WHITE);
+            let seconds = ticks.div_ceil(TICK_RATE);
+            draw_text(seconds.to_string(), x + CAPSULE_SIZE.x + 8.0, y + CAPSULE_SIZE.y, 24.0, WHITE);
+        }
+    }
+
+    /// Draws a menu over the dimmed game, with the selected item highlighted.
+    pub fn draw_menu(&self, title: &str, items: &[Item], selected: usize) {
+        draw_rectangle(0.0, 0.0, VIRTUAL_WIDTH, VIRTUAL_HEIGHT, SHADE);
+        self.label_centered(title, MENU_TOP - 160.0);
+        for (i, item) in items.iter().enumerate() {
+            let text = match &item.value {
+                Some(value) => format!("{}: {}", item.label, value),
+                None => item.label.clone(),
+            };
+            let color = if i == selected { SKYBLUE } else { WHITE };
+            let size = measure_text(&text, None, self.font_size, 1.0);
+            let x = VIRTUAL_WIDTH / 2.0 - size.width / 2.0;
+            draw_text(&text, x, item_y(i) + size.offset_y, self.font_size as f32, color);
+            if i == selected {
+                draw_text(">", x - 48.0, item_y(i) + size.offset_y, self.font_size as f32, SKYBLUE);
+            }
+        }
+        self.hint("Arrow keys or mouse to choose, Enter to select, Escape to go back", VIRTUAL_HEIGHT - 48.0);
+    }
+
+    pub fn draw_credits(&self, game: &Breakout) {
+        self.draw_panel(game);
+        self.label_centered("Breakout!", MENU_TOP - 160.0);
+        for (i, line) in CREDITS.iter().enumerate() {
+            self.hint(line, MENU_TOP + i as f32 * 48.0);
+        }
+        self.hint("Escape to go back", VIRTUAL_HEIGHT - 48.0);
+    }
+
+    pub fn draw_name_entry(&self, game: &Breakout, entry: &NameEntry) {
+        self.draw_panel(game);
+        let text = "New high score!";
+        self.label_centered(text, VIRTUAL_HEIGHT / 2.0 - 192.0);
+        let text = &format!("{:03}", game.score);
+        self.label_centered(text, VIRTUAL_HEIGHT / 2.0 - 128.0);
+
+        let spacing = 64.0;
+        let left = VIRTUAL_WIDTH / 2.0 - spacing * (entry.letters.len() as f32 - 1.0) / 2.0;
+        for (i, &letter) in entry.letters.iter().enumerate() {
+            let x = left + i as f32 * spacing;
+            let letter = (letter as char).to_string();
+            draw_text(&letter, x - 16.0, VIRTUAL_HEIGHT / 2.0, 64.0, WHITE);
+            if i == entry.cursor {
+                draw_rectangle(x - 20.0, VIRTUAL_HEIGHT / 2.0 + 12.0, 40.0, 6.0, SKYBLUE);
+            }
+        }
+
+        self.hint("Type or use the arrow keys, Enter to confirm", VIRTUAL_HEIGHT / 2.0 + 96.0);
+    }
+
+    /// Draws a ruleset's high-score table with a line of help under it.
+    pub fn draw_high_scores(&self, game: &Breakout, title: &str, ruleset: Ruleset, table: &[Entry], highlight: Option<usize>, hint: &str) {
+        self.draw_panel(game);
+        let top = VIRTUAL_HEIGHT * 0.15;
+        self.label_centered(title, top);
+        self.hint(ruleset.name(), top + 112.0);
+
+        for place in 0..TABLE_SIZE {
+            let line = match table.get(place) {
+                Some(entry) => format!("{:>2}. {}  {:03}", place + 1, entry.initials, entry.score),
+                None => format!("{:>2}. ---  ---", place + 1),
+            };
+            let color = if highlight == Some(place) { SKYBLUE } else { WHITE };
+            draw_text(&line, VIRTUAL_WIDTH / 2.0 - 110.0, top + 168.0 + place as f32 * 40.0, 40.0, color);
+        }
+
+        self.hint(hint, top + 200.0 + TABLE_SIZE as f32 * 40.0);
+    }
+
+    /// Draws both players' scores at the end of a game for two, with the winner's highlighted.
+    pub fn draw_two_player_results(&self, game: &Breakout, title: &str, hint: &str) {
+        self.draw_panel(game);
+        let top = VIRTUAL_HEIGHT * 0.15;
+        self.label_centered(title, top);
+        for player in 0..MAX_PLAYERS {
+            let line = format!("Player {}  {:03}", player + 1, game.player_score(player));
+            let color = if game.winner() == Some(player) { SKYBLUE } else { WHITE };
+            let center = get_text_center(&line, None, self.font_size, 1.0, 0.0);
+            draw_text(&line, VIRTUAL_WIDTH / 2.0 - center.x, top + 200.0 + player as f32 * 72.0, self.font_size as f32, color);
+        }
+        self.hint(hint, top + 232.0 + MAX_PLAYERS as f32 * 72.0);
+    }
+
+    /// Blanks out the playfield so a screen can be drawn over it.
+    fn draw_panel(&self, game: &Breakout) {
+        let offset = (VIRTUAL_WIDTH - game.size.x) / 2.0;
+        draw_rectangle(offset, 0.0, game.size.x, VIRTUAL_HEIGHT, BLACK);
+    }
+
+    /// Draws a line of small text centred across the screen, with its baseline at `y`.
+    fn hint(&self, text: &str, y: f32) {
+        let center = get_text_center(text, None, HINT_SIZE, 1.0, 0.0);
+        draw_text(text, VIRTUAL_WIDTH / 2.0 - center.x, y, HINT_SIZE as f32, WHITE);
+    }
+
+    /// Draws text in the large font with its top-left corner at `pos`.
+    fn label(&self, text: &str, pos: Vec2, color: Color) {
+        let size = measure_text(text, None, self.font_size, 1.0);
+        draw_text(text, pos.x, pos.y + size.offset_y, self.font_size as f32, color);
+    }
+
+    /// Draws text in the large font centred across the screen with its top at `y`.
+    fn label_centered(&self, text: &str, y: f32) {
+        let size = measure_text(text, None, self.font_size, 1.0);
+        self.label(text, vec2(VIRTUAL_WIDTH / 2.0 - size.width / 2.0, y), WHITE);
+    }
+}
@@ -0,0 +1,344 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::choice::Choice;
+use crate::paths::data_dir;
+use crate::rules::Ruleset;
+
+pub const TABLE_SIZE: usize = 10;
+pub const INITIALS: usize = 3;
+const FILE_NAME: &str = "highscores.txt";
+// what an unreadable table is renamed to, so that a fresh one doesn't overwrite it
+const SET_ASIDE_EXTENSION: &str = "corrupt";
+const MAGIC: &str = "breakout-highscores";
+const VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub initials: String,
+    pub score: u16,
+}
+
+/// The top scores for each ruleset, stored as a versioned text file with a checksum on the last
+/// line so that corruption and hand-editing can be detected.
+#[derive(Clone, Debug, Default)]
+pub struct HighScores {
+    tables: Vec<(Ruleset, Vec<Entry>)>,
+}
+
+#[derive(Debug)]
+pub enum HighScoreError {
+    Io(std::io::Error),
+    /// The file was written by a newer version of the game, or isn't a high-score file.
+    Version(String),
+    /// The contents don't match the checksum, so the file is corrupt or has been edited.
+    Checksum,
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for HighScoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HighScoreError::Io(err) => write!(f, "could not read high scores: {}", err),
+            HighScoreError::Version(header) => write!(f, "unsupported high-score file `{}`", header),
+            HighScoreError::Checksum => write!(f, "high-score checksum does not match"),
+            HighScoreError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for HighScoreError {}
+
+impl From<std::io::Error> for HighScoreError {
+    fn from(err: std::io::Error) -> Self {
+        HighScoreError::Io(err)
+    }
+}
+
+impl HighScores {
+    /// The high-score file in the user's data directory.
+    pub fn default_path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// Reads the table from `path`. A missing file is an empty table.
+    pub fn load(path: impl AsRef<Path>) -> Result<HighScores, HighScoreError> {
+        match fs::read_to_string(path) {
+            Ok(text) => HighScores::parse(&text),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(HighScores::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Renames a high-score file that couldn't be read out of the way, so the scores in it can
+    /// still be recovered by hand. Returns where it went.
+    pub fn set_aside(path: impl AsRef<Path>) -> std::io::Result<PathBuf> {
+        let path = path.as_ref();
+        let aside = path.with_extension(SET_ASIDE_EXTENSION);
+        fs::rename(path, &aside)?;
+        Ok(aside)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.to_text())
+    }
+
+    pub fn parse(text: &str) -> Result<HighScores, HighScoreError> {
+        let (body, checksum_line) = text.trim_end().rsplit_once('\n').ok_or(HighScoreError::Checksum)?;
+        let body = format!("{}\n", body);
+        let expected = checksum_line.strip_prefix("checksum ").and_then(|hex| u32::from_str_radix(hex, 16).ok());
+        if expected != Some(checksum(&body)) {
+            return Err(HighScoreError::Checksum);
+        }
+
+        let mut lines = body.lines();
+        let header = lines.next().unwrap_or_default();
+        if header != format!("{} {}", MAGIC, VERSION) {
+            return Err(HighScoreError::Version(header.to_owned()));
+        }
+
+        let mut scores = HighScores::default();
+        for (i, line) in lines.enumerate() {
+            let error = |message: String| HighScoreError::Parse { line: i + 2, message };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [ruleset, initials, score] = fields[..] else {
+                return Err(error("expected `<ruleset> <initials> <score>`".into()));
+            };
+            let ruleset = Ruleset::from_id(ruleset).ok_or_else(|| error(format!("unknown ruleset `{}`", ruleset)))?;
+            if !valid_initials(initials) {
+                return Err(error(format!("`{}` are not {} letters", initials, INITIALS)));
+            }
+            let score = score.parse().map_err(|_| error(format!("`{}` is not a score", score)))?;
+            scores.insert(ruleset, Entry { initials: initials.to_owned(), score });
+        }
+        Ok(scores)
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", MAGIC, VERSION);
+        for (ruleset, table) in &self.tables {
+            for entry in table {
+                text += &format!("{} {} {}\n", ruleset.id(), entry.initials, entry.score);
+            }
+        }
+        let sum = checksum(&text);
+        text + &format!("checksum {:08x}\n", sum)
+    }
+
+    /// The entries for a ruleset, best first.
+    pub fn table(&self, ruleset: Ruleset) -> &[Entry] {
+        self.tables.iter()
+            .find(|(r, _)| *r == ruleset)
+            .map_or(&[], |(_, table)| table)
+    }
+
+    /// Whether a score is good enough to make the table.
+    pub fn qualifies(&self, ruleset: Ruleset, score: u16) -> bool {
+        let table = self.table(ruleset);
+        score > 0 && (table.len() < TABLE_SIZE || table.iter().any(|entry| score > entry.score))
+    }
+
+    /// Adds an entry below any equal scores, and returns its place in the table if it made it.
+    pub fn insert(&mut self, ruleset: Ruleset, entry: Entry) -> Option<usize> {
+        let i = match self.tables.iter().position(|(r, _)| *r == ruleset) {
+            Some(i) => i,
+            None => {
+                self.tables.push((ruleset, Vec::new()));
+                self.tables.len() - 1
+            }
+        };
+        let table = &mut self.tables[i].1;
+        let place = table.iter().position(|other| entry.score > other.score).unwrap_or(table.len());
+        table.insert(place, entry);
+        table.truncate(TABLE_SIZE);
+        (place < TABLE_SIZE).then_some(place)
+    }
+}
+
+fn valid_initials(initials: &str) -> bool {
+    initials.len() == INITIALS && initials.bytes().all(|c| c.is_ascii_uppercase())
+}
+
+/// 32-bit FNV-1a.
+fn checksum(text: &str) -> u32 {
+    text.bytes().fold(0x811c_9dc5, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x0100_0193))
+}
+
+/// Arcade-style initials entry: each letter is picked in turn by cycling through the alphabet.
+#[derive(Clone, Debug)]
+pub struct NameEntry {
+    pub letters: [u8; INITIALS],
+    /// The letter being picked.
+    pub cursor: usize,
+}
+
+impl Default for NameEntry {
+    fn default() -> Self {
+        Self {
+            letters: [b'A'; INITIALS],
+            cursor: 0,
+        }
+    }
+}
+
+impl NameEntry {
+    /// Moves the current letter forwards or backwards through the alphabet, wrapping around.
+    pub fn cycle(&mut self, step: i8) {
+        let letter = &mut self.letters[self.cursor];
+        *letter = b'A' + (*letter - b'A' + (26 + step % 26) as u8) % 26;
+    }
+
+    /// Sets the current letter and moves to the next one. Returns true once all are entered.
+    pub fn type_letter(&mut self, letter: char) -> bool {
+        if letter.is_ascii_alphabetic() {
+            self.letters[self.cursor] = letter.to_ascii_uppercase() as u8;
+        }
+        self.confirm()
+    }
+
+    /// Accepts the current letter. Returns true once all are entered.
+    pub fn confirm(&mut self) -> bool {
+        self.cursor += 1;
+        if self.cursor == INITIALS {
+            self.cursor -= 1;
+            return true;
+        }
+        false
+    }
+
+    pub fn back(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn initials(&self) -> String {
+        String::from_utf8_lossy(&self.letters).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(initials: &str, score: u16) -> Entry {
+        Entry { initials: initials.to_owned(), score }
+    }
+
+    /// A file with the given lines and a checksum that matches them.
+    fn signed(body: &str) -> String {
+        format!("{}checksum {:08x}\n", body, checksum(body))
+    }
+
+    fn sample() -> HighScores {
+        let mut scores = HighScores::default();
+        scores.insert(Ruleset::Arcade, entry("ABC", 120));
+        scores.insert(Ruleset::Arcade, entry("XYZ", 340));
+        scores.insert(Ruleset::SingleWall, entry("DEF", 448));
+        scores
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let scores = sample();
+        let text = scores.to_text();
+        assert_eq!(text, signed("breakout-highscores 1\narcade XYZ 340\narcade ABC 120\nsingle-wall DEF 448\n"));
+        let again = HighScores::parse(&text).unwrap();
+        assert_eq!(again.table(Ruleset::Arcade), scores.table(Ruleset::Arcade));
+        assert_eq!(again.table(Ruleset::SingleWall), scores.table(Ruleset::SingleWall));
+        assert_eq!(again.to_text(), text);
+    }
+
+    #[test]
+    fn rejects_edited_and_truncated_files() {
+        let text = sample().to_text();
+        let edited = text.replace("XYZ 340", "XYZ 999");
+        assert!(matches!(HighScores::parse(&edited), Err(HighScoreError::Checksum)));
+        let cut = text.lines().next().unwrap();
+        assert!(matches!(HighScores::parse(cut), Err(HighScoreError::Checksum)));
+        let no_sum = text.replace("checksum ", "");
+        assert!(matches!(HighScores::parse(&no_sum), Err(HighScoreError::Checksum)));
+        assert!(matches!(HighScores::parse(""), Err(HighScoreError::Checksum)));
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        let text = signed("breakout-highscores 2\narcade ABC 1\n");
+        assert!(matches!(HighScores::parse(&text), Err(HighScoreError::Version(header)) if header == "breakout-highscores 2"));
+    }
+
+    #[test]
+    fn reports_bad_entries_with_their_line() {
+        let cases = [
+            ("arcade ABC", "expected `<ruleset> <initials> <score>`"),
+            ("classic ABC 10", "unknown ruleset `classic`"),
+            ("arcade abc 10", "`abc` are not 3 letters"),
+            ("arcade ABCD 10", "`ABCD` are not 3 letters"),
+            ("arcade ABC 70000", "`70000` is not a score"),
+        ];
+        for (line, expected) in cases {
+            let text = signed(&format!("breakout-highscores 1\narcade XYZ 5\n{}\n", line));
+            match HighScores::parse(&text) {
+                Err(HighScoreError::Parse { line, message }) => assert_eq!((line, message.as_str()), (3, expected)),
+                other => panic!("{:?} gave {:?}", line, other),
+            }
+        }
+    }
+
+    #[test]
+    fn insert_keeps_the_best_scores_in_order() {
+        let mut scores = HighScores::default();
+        for score in 1..=TABLE_SIZE as u16 {
+            assert_eq!(scores.insert(Ruleset::Arcade, entry("AAA", score * 10)), Some(0));
+        }
+        // ties go below the scores already there
+        assert_eq!(scores.insert(Ruleset::Arcade, entry("BBB", 50)), Some(6));
+        assert_eq!(scores.table(Ruleset::Arcade).len(), TABLE_SIZE);
+        assert_eq!(scores.table(Ruleset::Arcade)[6], entry("BBB", 50));
+        assert_eq!(scores.table(Ruleset::Arcade).last(), Some(&entry("AAA", 20)));
+
+        assert!(!scores.qualifies(Ruleset::Arcade, 20));
+        assert!(scores.qualifies(Ruleset::Arcade, 21));
+        assert_eq!(scores.insert(Ruleset::Arcade, entry("CCC", 20)), None);
+        assert!(scores.table(Ruleset::SingleWall).is_empty());
+        assert!(scores.qualifies(Ruleset::SingleWall, 1));
+        assert!(!scores.qualifies(Ruleset::SingleWall, 0));
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let dir = std::env::temp_dir().join(format!("breakout-highscore-test-{}", std::process::id()));
+        let path = dir.join(FILE_NAME);
+        assert!(HighScores::load(&path).unwrap().table(Ruleset::Arcade).is_empty());
+        sample().save(&path).unwrap();
+        let loaded = HighScores::load(&path).unwrap();
+        assert_eq!(loaded.to_text(), sample().to_text());
+
+        fs::write(&path, sample().to_text().replace("ABC", "ABD")).unwrap();
+        assert!(matches!(HighScores::load(&path), Err(HighScoreError::Checksum)));
+        let aside = HighScores::set_aside(&path).unwrap();
+        assert!(!path.exists());
+        assert_eq!(aside, dir.join("highscores.corrupt"));
+        assert!(fs::read_to_string(&aside).unwrap().contains("ABD"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn name_entry_wraps_around_the_alphabet() {
+        let mut name = NameEntry::default();
+        name.cycle(-1);
+        assert!(!name.confirm());
+        name.cycle(27);
+        assert!(!name.confirm());
+        assert!(name.type_letter('q'));
+        assert_eq!(name.initials(), "ZBQ");
+        assert_eq!(name.cursor, 2);
+        name.back();
+        name.back();
+        name.back();
+        assert_eq!(name.cursor, 0);
+    }
+}
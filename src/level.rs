@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use macroquad::color::{BLUE, Color, GRAY, GREEN, ORANGE, PINK, PURPLE, RED, SKYBLUE, WHITE, YELLOW};
+use macroquad::math::vec2;
+
+use crate::breakout::{BRICK_COUNT, BRICK_GAP, BRICK_SIZE, Brick, BrickKind, Fnv, PADDING};
+
+// rows below this would crowd the paddle
+pub const MAX_ROWS: usize = 16;
+const EMPTY: char = '.';
+// characters that can't stand for a brick, since a layout row starting with them would be misread
+const RESERVED: [char; 3] = [EMPTY, '#', '['];
+
+/// What a symbol in a level layout stands for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BrickType {
+    pub kind: BrickKind,
+    pub color: Color,
+    pub hits: u8,
+    pub points: u16,
+}
+
+impl BrickType {
+    /// A brick of this type at the given layout cell. Rows are numbered from the bottom of the
+    /// wall, so row 0 is the one nearest the paddle.
+    fn place(&self, row: u8, line: usize, col: usize) -> Brick {
+        let x = col as f32 * (BRICK_SIZE.x + BRICK_GAP);
+        let y = PADDING + line as f32 * (BRICK_SIZE.y + BRICK_GAP);
+        Brick {
+            pos: vec2(x, y),
+            row,
+            kind: self.kind,
+            color: self.color,
+            hits: self.hits,
+            max_hits: self.hits,
+            points: self.points,
+        }
+    }
+}
+
+/// A brick layout, read from a level file.
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub name: String,
+    /// Layout rows from top to bottom, each holding the brick type of every column.
+    pub cells: Vec<Vec<Option<BrickType>>>,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level::parse(include_str!("../levels/classic.level")).expect("built-in level is valid")
+    }
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(std::io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(err) => write!(f, "could not read level: {}", err),
+            LevelError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
+impl From<std::io::Error> for LevelError {
+    fn from(err: std::io::Error) -> Self {
+        LevelError::Io(err)
+    }
+}
+
+enum Section {
+    Header,
+    Bricks,
+    Layout,
+}
+
+impl Level {
+    pub fn load(path: impl AsRef<Path>) -> Result<Level, LevelError> {
+        Level::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a level file. See `levels/classic.level` for the format.
+    pub fn parse(text: &str) -> Result<Level, LevelError> {
+        let mut name = String::new();
+        let mut types = HashMap::new();
+        let mut layout: Vec<(usize, &str)> = Vec::new();
+        let mut section = Section::Header;
+
+        for (i, raw) in text.lines().enumerate() {
+            let line = i + 1;
+            let error = |message: String| LevelError::Parse { line, message };
+            let trimmed = raw.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            match trimmed {
+                "[bricks]" => section = Section::Bricks,
+                "[layout]" => section = Section::Layout,
+                _ if trimmed.starts_with('[') => return Err(error(format!("unknown section {}", trimmed))),
+                _ => match section {
+                    Section::Header => {
+                        let (key, value) = split_pair(trimmed, '=').ok_or_else(|| error("expected `key = value`".into()))?;
+                        match key {
+                            "name" => name = value.to_owned(),
+                            _ => return Err(error(format!("unknown setting `{}`", key))),
+                        }
+                    }
+                    Section::Bricks => {
+                        let (symbol, spec) = split_pair(trimmed, '=').ok_or_else(|| error("expected `<symbol> = <brick>`".into()))?;
+                        let mut chars = symbol.chars();
+                        let symbol = match (chars.next(), chars.next()) {
+                            (Some(c), None) if !RESERVED.contains(&c) => c,
+                            _ => return Err(error(format!("`{}` is not a single character other than {:?}", symbol, RESERVED))),
+                        };
+                        let brick_type = parse_brick_type(spec).map_err(error)?;
+                        if types.insert(symbol, brick_type).is_some() {
+                            return Err(error(format!("symbol `{}` is defined twice", symbol)));
+                        }
+                    }
+                    // layout rows keep their inner spacing, but a trailing comment is not allowed
+                    Section::Layout => layout.push((line, trimmed)),
+                },
+            }
+        }
+
+        if layout.is_empty() {
+            return Err(LevelError::Parse { line: text.lines().count(), message: "level has no [layout] rows".into() });
+        }
+        if layout.len() > MAX_ROWS {
+            let (line, _) = layout[MAX_ROWS];
+            return Err(LevelError::Parse { line, message: format!("a level has at most {} rows", MAX_ROWS) });
+        }
+
+        let mut cells = Vec::new();
+        for (line, row) in layout {
+            if row.chars().count() > BRICK_COUNT as usize {
+                return Err(LevelError::Parse { line, message: format!("a row has at most {} columns", BRICK_COUNT) });
+            }
+            let row = row.chars()
+                .map(|c| match c {
+                    EMPTY => Ok(None),
+                    c => types.get(&c).cloned().map(Some)
+                        .ok_or_else(|| LevelError::Parse { line, message: format!("symbol `{}` is not defined in [bricks]", c) }),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            cells.push(row);
+        }
+
+        Ok(Level { name, cells })
+    }
+
+    /// A hash of the level as text, to tell levels apart by. The built-in level's is
+    /// `Level::default().hash()`.
+    pub fn hash(&self) -> u64 {
+        let mut hash = Fnv::new();
+        hash.write(self.to_text().as_bytes());
+        hash.0
+    }
+
+    /// Writes the level back out in the level file format.
+    pub fn to_text(&self) -> String {
+        let mut types: Vec<&BrickType> = Vec::new();
+        for brick_type in self.cells.iter().flatten().flatten() {
+            if !types.contains(&brick_type) {
+                types.push(brick_type);
+            }
+        }
+        // any character works as a symbol, but readable ones are nicer to look at
+        let symbols: Vec<char> = ('A'..='Z').chain('a'..='z').chain('0'..='9').chain('\u{100}'..=char::MAX)
+            .take(types.len())
+            .collect();
+
+        let mut text = format!("name = {}\n\n[bricks]\n", self.name);
+        for (brick_type, symbol) in types.iter().zip(&symbols) {
+            let kind = match brick_type.kind {
+                BrickKind::Normal => "normal",
+                BrickKind::Steel => "steel",
+                BrickKind::Explosive => "explosive",
+            };
+            let [r, g, b, _]: [u8; 4] = brick_type.color.into();
+            text += &format!("{} = kind={} color=#{:02x}{:02x}{:02x} hits={} points={}\n",
+                             symbol, kind, r, g, b, brick_type.hits, brick_type.points);
+        }
+        text += "\n[layout]\n";
+        for row in &self.cells {
+            for cell in row {
+                text.push(match cell {
+                    Some(brick_type) => symbols[types.iter().position(|t| *t == brick_type).unwrap()],
+                    None => EMPTY,
+                });
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Builds a fresh wall of bricks from the layout.
+    pub fn bricks(&self) -> Vec<Brick> {
+        let rows = self.cells.len();
+        let mut list = Vec::new();
+        for (line, cells) in self.cells.iter().enumerate() {
+            for (col, cell) in cells.iter().enumerate() {
+                if let Some(brick_type) = cell {
+                    list.push(brick_type.place((rows - line - 1) as u8, line, col));
+                }
+            }
+        }
+        list
+    }
+}
+
+fn split_pair(text: &str, separator: char) -> Option<(&str, &str)> {
+    let (key, value) = text.split_once(separator)?;
+    Some((key.trim(), value.trim()))
+}
+
+fn parse_brick_type(spec: &str) -> Result<BrickType, String> {
+    let mut kind = BrickKind::Normal;
+    let mut color = None;
+    let mut hits = 1;
+    let mut points = 1;
+    for field in spec.split_whitespace() {
+        let (key, value) = split_pair(field, '=').ok_or_else(|| format!("expected `key=value`, found `{}`", field))?;
+        match key {
+            "kind" => kind = match value {
+                "normal" => BrickKind::Normal,
+                "steel" => BrickKind::Steel,
+                "explosive" => BrickKind::Explosive,
+                _ => return Err(format!("kind must be normal, steel or explosive, found `{}`", value)),
+            },
+            "color" => color = Some(parse_color(value)?),
+            "hits" => hits = value.parse().ok().filter(|&hits| hits > 0)
+                .ok_or_else(|| format!("hits must be a number from 1 to 255, found `{}`", value))?,
+            "points" => points = value.parse().map_err(|_| format!("points must be a number from 0 to 65535, found `{}`", value))?,
+            _ => return Err(format!("unknown brick field `{}`", key)),
+        }
+    }
+    let color = color.ok_or("brick has no color")?;
+    Ok(BrickType { kind, color, hits, points })
+}
+
+fn parse_color(value: &str) -> Result<Color, String> {
+    if let Some(hex) = value.strip_prefix('#') {
+        return match u32::from_str_radix(hex, 16) {
+            Ok(rgb) if hex.len() == 6 => Ok(Color::from_hex(rgb)),
+            _ => Err(format!("`{}` is not a #rrggbb color", value)),
+        };
+    }
+    Ok(match value {
+        "red" => RED,
+        "orange" => ORANGE,
+        "yellow" => YELLOW,
+        "green" => GREEN,
+        "blue" => BLUE,
+        "skyblue" => SKYBLUE,
+        "purple" => PURPLE,
+        "pink" => PINK,
+        "gray" | "grey" => GRAY,
+        "white" => WHITE,
+        _ => return Err(format!("unknown color `{}`", value)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The line and message of the error a level fails to parse with.
+    fn parse_error(text: &str) -> (usize, String) {
+        match Level::parse(text) {
+            Err(LevelError::Parse { line, message }) => (line, message),
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("level parsed:\n{}", text),
+        }
+    }
+
+    #[test]
+    fn parses_the_built_in_levels() {
+        let classic = Level::default();
+        assert_eq!(classic.name, "Classic");
+        assert_eq!(classic.cells.len(), 8);
+        assert!(classic.cells.iter().all(|row| row.len() == BRICK_COUNT as usize));
+        let red = classic.cells[0][0].as_ref().unwrap();
+        assert_eq!((red.kind, red.color, red.hits, red.points), (BrickKind::Normal, RED, 1, 7));
+
+        Level::parse(include_str!("../levels/fortress.level")).unwrap();
+    }
+
+    #[test]
+    fn numbers_rows_from_the_bottom() {
+        let level = Level::parse("[bricks]\nA = color=red\nB = color=blue hits=3 kind=steel\n[layout]\nA.A\n.B\n").unwrap();
+        let bricks = level.bricks();
+        assert_eq!(bricks.len(), 3);
+        assert_eq!(bricks.iter().map(|brick| brick.row).collect::<Vec<_>>(), [1, 1, 0]);
+        assert_eq!(bricks[1].pos.x, 2.0 * (BRICK_SIZE.x + BRICK_GAP));
+        assert!(bricks[2].pos.y > bricks[0].pos.y);
+        assert_eq!((bricks[2].kind, bricks[2].hits, bricks[2].max_hits), (BrickKind::Steel, 3, 3));
+    }
+
+    #[test]
+    fn to_text_round_trips() {
+        let text = "name = Mixed\n[bricks]\nx = color=#123456 points=0 kind=explosive\n# a comment\ny = color=#808080 hits=255 points=65535\n[layout]\n..x\nyyy.y\n\n";
+        let level = Level::parse(text).unwrap();
+        let again = Level::parse(&level.to_text()).unwrap();
+        assert_eq!(again.name, level.name);
+        assert_eq!(again.cells, level.cells);
+        assert_eq!(again.hash(), level.hash());
+        assert_ne!(level.hash(), Level::default().hash());
+    }
+
+    #[test]
+    fn named_colors_settle_after_one_round_trip() {
+        // names can be between two #rrggbb colors, so only the text written back is stable
+        let level = Level::default();
+        let again = Level::parse(&level.to_text()).unwrap();
+        assert_eq!(again.to_text(), level.to_text());
+        assert_eq!(again.hash(), level.hash());
+    }
+
+    #[test]
+    fn reports_errors_with_their_line() {
+        let cases = [
+            ("[colors]", 1, "unknown section [colors]"),
+            ("name Classic", 1, "expected `key = value`"),
+            ("speed = 2", 1, "unknown setting `speed`"),
+            ("[bricks]\nA red", 2, "expected `<symbol> = <brick>`"),
+            ("[bricks]\nAB = color=red", 2, "`AB` is not a single character"),
+            ("[bricks]\n. = color=red", 2, "`.` is not a single character"),
+            ("[bricks]\nA = color=red\nA = color=blue", 3, "symbol `A` is defined twice"),
+            ("[bricks]\nA = points=2", 2, "brick has no color"),
+            ("[bricks]\nA = color=#12345", 2, "`#12345` is not a #rrggbb color"),
+            ("[bricks]\nA = color=teal", 2, "unknown color `teal`"),
+            ("[bricks]\nA = color=red kind=glass", 2, "kind must be normal, steel or explosive"),
+            ("[bricks]\nA = color=red hits=0", 2, "hits must be a number from 1 to 255"),
+            ("[bricks]\nA = color=red hits=256", 2, "hits must be a number from 1 to 255"),
+            ("[bricks]\nA = color=red points=-1", 2, "points must be a number from 0 to 65535"),
+            ("[bricks]\nA = color=red size=2", 2, "unknown brick field `size`"),
+            ("[bricks]\nA = color=red points", 2, "expected `key=value`, found `points`"),
+            ("[bricks]\nA = color=red\n\n[layout]\nA\nAB", 6, "symbol `B` is not defined in [bricks]"),
+            ("[bricks]\nA = color=red\n[layout]\nAAAAAAAAAAAAAAA", 4, "a row has at most 14 columns"),
+            ("name = Empty\n[bricks]\nA = color=red\n", 3, "level has no [layout] rows"),
+        ];
+        for (text, line, message) in cases {
+            let (error_line, error) = parse_error(text);
+            assert_eq!(error_line, line, "{:?}", text);
+            assert!(error.starts_with(message), "{:?} gave `{}`", text, error);
+        }
+    }
+
+    #[test]
+    fn limits_the_number_of_rows() {
+        let mut text = String::from("[bricks]\nA = color=red\n[layout]\n");
+        for _ in 0..MAX_ROWS {
+            text += "A\n";
+        }
+        assert_eq!(Level::parse(&text).unwrap().cells.len(), MAX_ROWS);
+        text += "A\n";
+        assert_eq!(parse_error(&text), (4 + MAX_ROWS, format!("a level has at most {} rows", MAX_ROWS)));
+    }
+
+    #[test]
+    fn load_reports_missing_files() {
+        assert!(matches!(Level::load("levels/missing.level"), Err(LevelError::Io(_))));
+    }
+}
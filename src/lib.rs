@@ -0,0 +1,16 @@
+pub mod ai;
+pub mod audio;
+pub mod breakout;
+pub mod choice;
+pub mod collision;
+pub mod env;
+pub mod highscore;
+pub mod level;
+pub mod paths;
+pub mod powerup;
+pub mod raster;
+pub mod replay;
+pub mod rng;
+pub mod rules;
+pub mod save;
+pub mod synth;
@@ -1,82 +1,305 @@
-use macroquad::color::{Color, WHITE};
-use macroquad::input::{is_key_pressed, is_mouse_button_pressed, KeyCode, MouseButton, show_mouse};
-use macroquad::ui::{root_ui, Skin};
+use macroquad::input::{is_mouse_button_pressed, MouseButton, show_mouse};
+use macroquad::math::vec2;
+use macroquad::time::get_frame_time;
 use macroquad::window::{Conf, next_frame};
 
-use crate::breakout::{Breakout, GameState};
+use std::path::Path;
+use std::process::{exit, ExitCode};
 
-mod breakout;
+use breakout::audio::{Audio, SAMPLE_RATE, Sound};
+use breakout::breakout::{GAME_HEIGHT, GAME_WIDTH, GameState, Outcome, Tuning};
+use breakout::choice::Choice;
+use breakout::level::Level;
+use breakout::replay::{Playback, Replay};
+use breakout::raster::{self, Canvas};
+use breakout::synth;
 
-fn window_conf() -> Conf {
+use crate::app::{App, handle_audio_keys};
+use crate::config::{Config, USAGE};
+use crate::controls::{Action, Controls};
+use crate::frontend::Frontend;
+use crate::menu::{Item, MenuEvent, Pointer};
+use crate::render::RenderOptions;
+use crate::screen::Screen;
+
+mod app;
+mod config;
+mod controls;
+mod frontend;
+mod menu;
+mod render;
+mod scene;
+mod screen;
+
+fn window_conf(config: &Config) -> Conf {
     Conf {
         window_title: "Breakout!".to_owned(),
-        fullscreen: true,
+        window_width: config.width as i32,
+        window_height: config.height as i32,
+        window_resizable: true,
+        fullscreen: config.fullscreen,
         ..Default::default()
     }
 }
 
 const FONT_SIZE: u16 = 56;
+const THUMBNAIL_WIDTH: usize = 320;
+const THUMBNAIL_HEIGHT: usize = 240;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().collect();
+    match (args.get(1).map(String::as_str), args.get(2)) {
+        (Some("verify"), _) => match &args[2..] {
+            [path] => verify(path, false),
+            [flag, path] if flag == "--standard" => verify(path, true),
+            _ => usage("breakout verify [--standard] <replay>"),
+        },
+        (Some("export-sounds"), Some(dir)) => export_sounds(dir),
+        (Some("export-sounds"), None) => usage("breakout export-sounds <dir>"),
+        (Some("thumbnail"), Some(path)) if args.len() == 4 => thumbnail(path, &args[3]),
+        (Some("thumbnail"), _) => usage("breakout thumbnail <replay> <png>"),
+        (Some("render"), _) => render(&args[2..]),
+        _ => play(&args[1..]),
+    }
+}
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let skin = skin(FONT_SIZE);
-    root_ui().push_skin(&skin);
+/// Opens the game window with the settings from the config file and command line. Bad
+/// settings are reported before the window opens.
+fn play(args: &[String]) -> ExitCode {
+    let config = match Config::from_args(args) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let level = match &config.level {
+        Some(path) => match Level::load(path) {
+            Ok(level) => level,
+            Err(err) => {
+                eprintln!("{}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Level::default(),
+    };
+    let replay = match config.replay.as_ref().map(|path| (path, Replay::load(path))) {
+        Some((_, Ok(replay))) => Some(replay),
+        Some((path, Err(err))) => {
+            eprintln!("{}: {}", path.display(), err);
+            return ExitCode::FAILURE;
+        }
+        None => None,
+    };
+    macroquad::Window::from_config(window_conf(&config), run(config, level, replay));
+    ExitCode::SUCCESS
+}
 
-    let mut game = Breakout::new(FONT_SIZE);
+fn usage(text: &str) -> ExitCode {
+    eprintln!("usage: {}", text);
+    ExitCode::FAILURE
+}
 
-    loop {
-        if game.game_state != GameState::Paused {
-            game.update();
+/// Writes every sound effect to `<dir>/<name>.wav`.
+fn export_sounds(dir: &str) -> ExitCode {
+    if let Err(err) = std::fs::create_dir_all(dir) {
+        eprintln!("{}: {}", dir, err);
+        return ExitCode::FAILURE;
+    }
+    for sound in Sound::all() {
+        let path = Path::new(dir).join(format!("{}.wav", sound.name()));
+        if let Err(err) = synth::write_wav(&path, &sound.samples(), SAMPLE_RATE) {
+            eprintln!("{}: {}", path.display(), err);
+            return ExitCode::FAILURE;
+        }
+        println!("{}", path.display());
+    }
+    ExitCode::SUCCESS
+}
+
+/// Draws the last frame of a replay to a PNG, for showing it in a list.
+fn thumbnail(path: &str, out: &str) -> ExitCode {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut playback = Playback::new(replay);
+    playback.run_to_end();
+    let mut canvas = Canvas::new(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT);
+    raster::draw_screen(&mut canvas, &playback.game);
+    if let Err(err) = canvas.write_png(out) {
+        eprintln!("{}: {}", out, err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+/// Renders a replay to an animated GIF or a directory of PNGs.
+fn render(args: &[String]) -> ExitCode {
+    let options = match RenderOptions::from_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}", err);
+            return usage(render::USAGE);
+        }
+    };
+    match render::render(&options) {
+        Ok(frames) => {
+            println!("{}: {} frames", options.output.display(), frames);
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
         }
-        if game.game_state != GameState::Playing {
-            game.exit_button();
+    }
+}
+
+/// Plays a replay without a window and checks that it ends the way its header claims, so that
+/// a submitted score can be trusted. The game is played with the settings in the file, so they
+/// are printed too, and `standard` rejects any but the built-in level, default tuning and
+/// normal playfield.
+fn verify(path: &str, standard: bool) -> ExitCode {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let level = replay.level.hash();
+    let builtin = level == Level::default().hash();
+    println!("ruleset: {}", replay.ruleset.id());
+    println!("seed: {}", replay.seed);
+    println!("players: {}", replay.tuning.players);
+    println!("lives: {}", replay.tuning.lives);
+    println!("ball speed: {}", replay.tuning.speed);
+    println!("paddle angles: {} to {}", replay.tuning.deflection.min_angle, replay.tuning.deflection.max_angle);
+    println!("playfield: {}x{}", replay.size.x, replay.size.y);
+    println!("level: {:016x} ({})", level, if builtin { "built-in" } else { "custom" });
+    if standard {
+        let mut differences = Vec::new();
+        if !builtin {
+            differences.push("a custom level");
+        }
+        if replay.tuning != Tuning::default() {
+            differences.push("changed lives, ball speed, paddle angles or players");
+        }
+        if replay.size != vec2(GAME_WIDTH, GAME_HEIGHT) {
+            differences.push("a different playfield size");
         }
+        if !differences.is_empty() {
+            eprintln!("{}: not a standard game, it has {}", path, differences.join(" and "));
+            return ExitCode::FAILURE;
+        }
+    }
 
-        handle_mouse_click(&mut game);
-        handle_key(&mut game);
-        show_mouse(game.game_state != GameState::Playing);
+    let mut playback = Playback::new(replay.clone());
+    playback.run_to_end();
+    let game = &playback.game;
+    let outcome = game.outcome.unwrap_or(Outcome { score: game.score, balls_rem: game.balls_rem, hash: game.state_hash() });
+    println!("score: {}", outcome.score);
+    println!("balls left: {}", outcome.balls_rem);
+    println!("state hash: {:016x}", outcome.hash);
 
-        game.draw();
+    if game.outcome.is_none() {
+        eprintln!("{}: the game doesn't end", path);
+        return ExitCode::FAILURE;
+    }
+    if game.inputs.len() != replay.inputs.len() {
+        eprintln!("{}: the game ends before the last recorded tick", path);
+        return ExitCode::FAILURE;
+    }
+    match replay.outcome {
+        Some(claimed) if claimed == outcome => ExitCode::SUCCESS,
+        Some(claimed) => {
+            eprintln!(
+                "{}: claims score {}, balls left {}, state hash {:016x}",
+                path, claimed.score, claimed.balls_rem, claimed.hash
+            );
+            ExitCode::FAILURE
+        }
+        None => {
+            eprintln!("{}: no result to check against", path);
+            ExitCode::FAILURE
+        }
+    }
+}
 
+async fn run(config: Config, level: Level, replay: Option<Replay>) {
+    let screen = Screen::new(config.integer_scaling);
+    let mut audio = open_audio().await;
+    if let Some(replay) = replay {
+        audio.set_volume(config.volume);
+        audio.muted = config.muted;
+        watch(Playback::new(replay), &config, &mut Frontend::new(FONT_SIZE), &mut audio, &screen).await
+    }
+    let mut app = App::new(config, level, screen, audio, Frontend::new(FONT_SIZE));
+    loop {
+        app.update();
+        app.draw();
         next_frame().await
     }
 
-    fn handle_mouse_click(game: &mut Breakout) {
-        if is_mouse_button_pressed(MouseButton::Left) && game.game_state != GameState::Playing {
-            if game.game_state == GameState::GameOver || game.game_state == GameState::Win {
-                *game = Breakout::new(FONT_SIZE);
+    /// Plays back a recorded game until the window is closed. Pausing, or the end of the
+    /// recording, brings up a menu to watch it again or quit.
+    async fn watch(mut playback: Playback, config: &Config, frontend: &mut Frontend, audio: &mut Audio, screen: &Screen) -> ! {
+        let mut controls = Controls::new(config.bindings.clone());
+        let mut selected = 0;
+        let mut last_mouse = screen.mouse_position();
+        loop {
+            controls.input(screen.scale());
+            if playback.game.game_state == GameState::Playing {
+                playback.update(get_frame_time());
+            }
+            audio.play_all(playback.game.sounds.drain(..));
+            handle_audio_keys(audio, frontend, &controls);
+
+            let ended = playback.finished() || matches!(playback.game.game_state, GameState::GameOver | GameState::Win);
+            let paused = playback.game.game_state == GameState::Paused;
+            if !ended && controls.pressed(Action::Pause) {
+                playback.game.game_state = if paused { GameState::Playing } else { GameState::Paused };
+                selected = 0;
+            } else if ended || paused {
+                let mouse = screen.mouse_position();
+                let pointer = Pointer { pos: mouse, moved: mouse != last_mouse, clicked: is_mouse_button_pressed(MouseButton::Left) };
+                last_mouse = mouse;
+                match menu::navigate(&mut selected, 2, controls.nav(), &pointer) {
+                    Some(MenuEvent::Select(0)) if ended => playback = Playback::new(playback.replay.clone()),
+                    Some(MenuEvent::Select(0) | MenuEvent::Back) if !ended => playback.game.game_state = GameState::Playing,
+                    Some(MenuEvent::Select(1)) => exit(0),
+                    _ => {}
+                }
             }
-            game.game_state = GameState::Playing;
+            let menu = playback.game.game_state != GameState::Playing || playback.finished();
+            show_mouse(menu);
+
+            screen.begin();
+            frontend.draw(&playback.game);
+            if menu {
+                let (title, first) = if ended { ("Replay over", "Watch again") } else { ("Replay paused", "Resume") };
+                frontend.draw_menu(title, &[Item::new(first), Item::new("Quit")], selected);
+            }
+            screen.end();
+
+            next_frame().await
         }
     }
 
-    fn handle_key(game: &mut Breakout) {
-        if is_key_pressed(KeyCode::Escape) {
-            game.game_state = match game.game_state.clone() {
-                GameState::Playing => GameState::Paused,
-                GameState::Paused => GameState::Playing,
-                other => other
-            };
-        }
+    #[cfg(feature = "audio")]
+    async fn open_audio() -> Audio {
+        Audio::new(breakout::audio::MacroquadBackend::load().await)
     }
 
-    fn skin(font_size: u16) -> Skin {
-        let transparent = Color::from_rgba(0, 0, 0, 0);
-        let label_style = root_ui().style_builder()
-            .font_size(font_size)
-            .text_color(WHITE)
-            .build();
-        let button_style = root_ui().style_builder()
-            .font_size(font_size)
-            .color(transparent)
-            .color_hovered(transparent)
-            .text_color(WHITE)
-            .text_color_hovered(WHITE)
-            .build();
-        Skin {
-            label_style,
-            button_style,
-            ..root_ui().default_skin()
-        }
+    #[cfg(not(feature = "audio"))]
+    async fn open_audio() -> Audio {
+        Audio::null()
     }
 }
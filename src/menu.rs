@@ -0,0 +1,75 @@
+use macroquad::math::Vec2;
+
+use crate::controls::Nav;
+use crate::screen::VIRTUAL_WIDTH;
+
+// menu layout in virtual pixels
+pub const MENU_TOP: f32 = 360.0;
+pub const ITEM_HEIGHT: f32 = 64.0;
+const ITEM_WIDTH: f32 = 760.0;
+
+/// One line of a menu, with the current value if it is a setting.
+pub struct Item {
+    pub label: String,
+    pub value: Option<String>,
+}
+
+impl Item {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self { label: label.into(), value: None }
+    }
+
+    pub fn setting(label: impl Into<String>, value: impl Into<String>) -> Self {
+        Self { label: label.into(), value: Some(value.into()) }
+    }
+}
+
+/// Where the mouse is, in virtual pixels, and what it did this frame.
+pub struct Pointer {
+    pub pos: Vec2,
+    pub moved: bool,
+    pub clicked: bool,
+}
+
+/// What the player did with a menu this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuEvent {
+    Select(usize),
+    /// Left or right on an item, for changing a setting.
+    Adjust(usize, i8),
+    Back,
+}
+
+/// The top of item `i`.
+pub fn item_y(i: usize) -> f32 {
+    MENU_TOP + i as f32 * ITEM_HEIGHT
+}
+
+fn item_at(pos: Vec2, count: usize) -> Option<usize> {
+    if (pos.x - VIRTUAL_WIDTH / 2.0).abs() > ITEM_WIDTH / 2.0 || pos.y < MENU_TOP {
+        return None;
+    }
+    let i = ((pos.y - MENU_TOP) / ITEM_HEIGHT) as usize;
+    (i < count).then_some(i)
+}
+
+/// Moves the selection of a menu with `count` items. Keys and buttons move it one item at a
+/// time, wrapping around, and the mouse selects whatever it moves over.
+pub fn navigate(selected: &mut usize, count: usize, nav: Option<Nav>, pointer: &Pointer) -> Option<MenuEvent> {
+    let hovered = item_at(pointer.pos, count);
+    if let (true, Some(i)) = (pointer.moved, hovered) {
+        *selected = i;
+    }
+    if pointer.clicked {
+        return hovered.map(MenuEvent::Select);
+    }
+    match nav? {
+        Nav::Up => *selected = (*selected + count - 1) % count,
+        Nav::Down => *selected = (*selected + 1) % count,
+        Nav::Left => return Some(MenuEvent::Adjust(*selected, -1)),
+        Nav::Right => return Some(MenuEvent::Adjust(*selected, 1)),
+        Nav::Select => return Some(MenuEvent::Select(*selected)),
+        Nav::Back => return Some(MenuEvent::Back),
+    }
+    None
+}
@@ -0,0 +1,31 @@
+use std::env;
+use std::path::PathBuf;
+
+const APP_DIR: &str = "breakout";
+
+/// Where the game keeps its data files: `$XDG_DATA_HOME/breakout`, falling back to
+/// `~/.local/share/breakout`, or `%APPDATA%\breakout` on Windows.
+pub fn data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join(APP_DIR));
+    }
+    xdg_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// Where the game looks for its config file: `$XDG_CONFIG_HOME/breakout`, falling back to
+/// `~/.config/breakout`, or `%APPDATA%\breakout` on Windows.
+pub fn config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join(APP_DIR));
+    }
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+fn xdg_dir(var: &str, home_fallback: &str) -> Option<PathBuf> {
+    let base = match env::var_os(var) {
+        // the spec says relative paths are to be ignored
+        Some(dir) if PathBuf::from(&dir).is_absolute() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(home_fallback),
+    };
+    Some(base.join(APP_DIR))
+}
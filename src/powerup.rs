@@ -0,0 +1,121 @@
+use macroquad::color::{BLUE, Color, GREEN, GRAY, PINK, RED, VIOLET};
+use macroquad::math::{Vec2, vec2};
+
+use crate::breakout::TICK_RATE;
+
+pub const CAPSULE_SIZE: Vec2 = vec2(40.0, 14.0);
+// falling speed in pixels per millisecond
+pub const CAPSULE_SPEED: f32 = 0.15;
+// one in this many destroyed bricks drops a capsule
+pub const DROP_CHANCE: u32 = 6;
+// how long timed effects last
+const EFFECT_TICKS: u32 = 15 * TICK_RATE;
+pub const EXPAND_FACTOR: f32 = 1.5;
+pub const SLOW_FACTOR: f32 = 0.7;
+pub const LASER_SIZE: Vec2 = vec2(4.0, 12.0);
+// laser speed in pixels per millisecond
+pub const LASER_SPEED: f32 = 1.0;
+pub const LASER_COOLDOWN: u32 = TICK_RATE / 3;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PowerUp {
+    /// Makes the paddle wider.
+    Expand,
+    /// Slows the ball down.
+    Slow,
+    /// Catches the ball on the paddle until it is launched again.
+    Catch,
+    /// Lets the paddle fire lasers that break bricks.
+    Laser,
+    /// Gives an extra ball.
+    ExtraLife,
+    /// Splits each ball in play into three.
+    MultiBall,
+}
+
+impl PowerUp {
+    /// Power-ups that can drop from bricks.
+    pub const ALL: [PowerUp; 6] = [
+        PowerUp::Expand,
+        PowerUp::Slow,
+        PowerUp::Catch,
+        PowerUp::Laser,
+        PowerUp::ExtraLife,
+        PowerUp::MultiBall,
+    ];
+
+    /// The letter shown on the capsule and in the HUD.
+    pub fn letter(&self) -> &'static str {
+        match self {
+            PowerUp::Expand => "E",
+            PowerUp::Slow => "S",
+            PowerUp::Catch => "C",
+            PowerUp::Laser => "L",
+            PowerUp::ExtraLife => "P",
+            PowerUp::MultiBall => "D",
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUp::Expand => BLUE,
+            PowerUp::Slow => GREEN,
+            PowerUp::Catch => PINK,
+            PowerUp::Laser => RED,
+            PowerUp::ExtraLife => GRAY,
+            PowerUp::MultiBall => VIOLET,
+        }
+    }
+
+    /// How long the effect lasts, or `None` if it takes effect once when caught.
+    pub fn duration(&self) -> Option<u32> {
+        match self {
+            PowerUp::ExtraLife | PowerUp::MultiBall => None,
+            _ => Some(EFFECT_TICKS),
+        }
+    }
+}
+
+/// A power-up falling towards the paddle.
+#[derive(Clone)]
+pub struct Capsule {
+    pub pos: Vec2,
+    pub kind: PowerUp,
+}
+
+/// The timed power-ups currently active and how many ticks each has left.
+#[derive(Clone, Default)]
+pub struct Effects {
+    pub active: Vec<(PowerUp, u32)>,
+}
+
+impl Effects {
+    pub fn is_active(&self, kind: PowerUp) -> bool {
+        self.active.iter().any(|&(active, _)| active == kind)
+    }
+
+    /// Starts an effect, or restarts its timer if it is already running.
+    pub fn activate(&mut self, kind: PowerUp, ticks: u32) {
+        match self.active.iter_mut().find(|(active, _)| *active == kind) {
+            Some(effect) => effect.1 = ticks,
+            None => self.active.push((kind, ticks)),
+        }
+    }
+
+    /// Counts down one tick and returns the effects that ran out.
+    pub fn tick(&mut self) -> Vec<PowerUp> {
+        let mut expired = Vec::new();
+        self.active.retain_mut(|(kind, ticks)| {
+            *ticks -= 1;
+            if *ticks == 0 {
+                expired.push(*kind);
+            }
+            *ticks > 0
+        });
+        expired
+    }
+
+    pub fn clear(&mut self) {
+        self.active.clear();
+    }
+}
@@ -0,0 +1,363 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use macroquad::color::{BLACK, Color, GRAY, RED, SKYBLUE, WHITE};
+use macroquad::math::{Vec2, vec2};
+
+use crate::breakout::{BALL_SIZE, BRICK_SIZE, BrickKind, Breakout, GAME_HEIGHT, TICK_RATE};
+use crate::powerup::{CAPSULE_SIZE, LASER_SIZE, PowerUp};
+
+/// The virtual screen the game is drawn to, 4:3 with the playfield running its full height.
+pub const SCREEN_SIZE: Vec2 = vec2(1280.0, GAME_HEIGHT);
+
+// the glyphs are 5 by 7 cells with a cell's gap between them
+const GLYPH_WIDTH: usize = 5;
+const GLYPH_HEIGHT: usize = 7;
+// cell sizes in screen pixels, chosen to match the frontend's text
+const HUD_CELL: f32 = 6.0;
+const CAPSULE_CELL: f32 = 2.0;
+const TIMER_CELL: f32 = 2.5;
+
+/// An RGBA image drawn on the CPU, for when there is no GPU to draw with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canvas {
+    pub width: usize,
+    pub height: usize,
+    /// Rows from the top, four bytes a pixel.
+    pub pixels: Vec<u8>,
+}
+
+impl Canvas {
+    /// A black canvas.
+    pub fn new(width: usize, height: usize) -> Self {
+        let mut pixels = vec![0; width * height * 4];
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+        Self { width, height, pixels }
+    }
+
+    pub fn clear(&mut self, color: Color) {
+        let rgba = rgba(color);
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&rgba);
+        }
+    }
+
+    /// Fills the pixels whose centres are inside the rectangle. Anything smaller than a pixel
+    /// still gets the one it is mostly in, so that nothing disappears when drawn small.
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        let Some((left, right)) = span(x, width, self.width) else {
+            return;
+        };
+        let Some((top, bottom)) = span(y, height, self.height) else {
+            return;
+        };
+        let rgba = rgba(color);
+        for row in top..bottom {
+            let start = (row * self.width + left) * 4;
+            let end = (row * self.width + right) * 4;
+            for pixel in self.pixels[start..end].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&rgba);
+            }
+        }
+    }
+
+    /// The outline of a rectangle, `thickness / 2` wide on the inside of its edges like
+    /// macroquad's `draw_rectangle_lines`.
+    pub fn stroke_rect(&mut self, x: f32, y: f32, width: f32, height: f32, thickness: f32, color: Color) {
+        let t = thickness / 2.0;
+        self.fill_rect(x, y, width, t, color);
+        self.fill_rect(x, y + height - t, width, t, color);
+        self.fill_rect(x, y + t, t, height - thickness, color);
+        self.fill_rect(x + width - t, y + t, t, height - thickness, color);
+    }
+
+    /// Draws digits and the power-up letters in a blocky 5 by 7 font, `cell` pixels to a
+    /// block, with the top left of the text at `(x, y)`. Other characters are left as spaces.
+    pub fn draw_text(&mut self, text: &str, x: f32, y: f32, cell: f32, color: Color) {
+        for (i, ch) in text.chars().enumerate() {
+            let Some(rows) = glyph(ch) else {
+                continue;
+            };
+            let left = x + i as f32 * (GLYPH_WIDTH + 1) as f32 * cell;
+            for (row, &bits) in rows.iter().enumerate() {
+                for col in 0..GLYPH_WIDTH {
+                    if bits >> (GLYPH_WIDTH - 1 - col) & 1 != 0 {
+                        self.fill_rect(left + col as f32 * cell, y + row as f32 * cell, cell, cell, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The pixels without the alpha channel, three bytes a pixel.
+    pub fn rgb(&self) -> Vec<u8> {
+        self.pixels.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect()
+    }
+
+    /// Saves the canvas as an RGBA PNG.
+    pub fn write_png(&self, path: impl AsRef<Path>) -> Result<(), png::EncodingError> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.write_header()?.write_image_data(&self.pixels)
+    }
+}
+
+/// Draws the playfield's paddle, balls and bricks, with the playfield's top left corner at
+/// `origin` and `scale` canvas pixels to a playfield unit on each axis. The balls are drawn
+/// `alpha` of the way from their last position to the current one, as in `Ball::render_pos`.
+pub fn draw_playfield(canvas: &mut Canvas, game: &Breakout, origin: Vec2, scale: Vec2, alpha: f32) {
+    let mut rect = |pos: Vec2, size: Vec2, color: Color| {
+        let pos = origin + pos * scale;
+        let size = size * scale;
+        canvas.fill_rect(pos.x, pos.y, size.x, size.y, color);
+    };
+    let paddle = game.paddle_rect();
+    rect(vec2(paddle.x, paddle.y), vec2(paddle.width, paddle.height), SKYBLUE);
+    for ball in &game.balls {
+        rect(ball.render_pos(alpha), BALL_SIZE, WHITE);
+    }
+    for brick in &game.bricks {
+        rect(brick.pos, BRICK_SIZE, brick.color());
+    }
+}
+
+/// Draws the game the way the window shows it during play: the border, playfield, power-ups
+/// and HUD, scaled from `SCREEN_SIZE` to fill the canvas.
+pub fn draw_screen(canvas: &mut Canvas, game: &Breakout) {
+    canvas.clear(BLACK);
+    let scale = vec2(canvas.width as f32, canvas.height as f32) / SCREEN_SIZE;
+    let offset = (SCREEN_SIZE.x - game.size.x) / 2.0;
+    let origin = vec2(offset, 0.0) * scale;
+    // everything below is in screen pixels
+    let at = |x: f32, y: f32| vec2(x, y) * scale;
+
+    let border = at(offset - 8.0, 0.0);
+    let size = at(game.size.x + 16.0, SCREEN_SIZE.y);
+    canvas.stroke_rect(border.x, border.y, size.x, size.y, 16.0 * scale.x, WHITE);
+    draw_playfield(canvas, game, origin, scale, game.alpha());
+    for brick in &game.bricks {
+        if brick.kind == BrickKind::Explosive {
+            let pos = origin + (brick.pos + 4.0) * scale;
+            let size = (BRICK_SIZE - 8.0) * scale;
+            canvas.stroke_rect(pos.x, pos.y, size.x, size.y, 2.0 * scale.x, BLACK);
+        }
+    }
+
+    for capsule in &game.capsules {
+        draw_capsule(canvas, capsule.kind, origin + capsule.pos * scale, scale);
+    }
+    for &laser in &game.lasers {
+        let pos = origin + laser * scale;
+        let size = LASER_SIZE * scale;
+        canvas.fill_rect(pos.x, pos.y, size.x, size.y, RED);
+    }
+    // the active power-ups and their seconds left, under the paddle
+    let y = game.paddle_pos.y + 64.0;
+    for (i, &(kind, ticks)) in game.effects.active.iter().enumerate() {
+        let x = offset + 16.0 + i as f32 * 96.0;
+        draw_capsule(canvas, kind, at(x, y), scale);
+        let seconds = ticks.div_ceil(TICK_RATE).to_string();
+        let pos = at(x + CAPSULE_SIZE.x + 8.0, y);
+        canvas.draw_text(&seconds, pos.x, pos.y, TIMER_CELL * scale.y, WHITE);
+    }
+
+    let cell = HUD_CELL * scale.y;
+    if game.waiting.is_none() {
+        let score = at(offset + 16.0, 32.0);
+        canvas.draw_text(&format!("{:03}", game.score), score.x, score.y, cell, WHITE);
+        let balls = at(offset + game.size.x - 100.0, 32.0);
+        canvas.draw_text(&game.balls_rem.to_string(), balls.x, balls.y, cell, WHITE);
+        return;
+    }
+    // both scores for two players, whoever isn't playing dimmed, and the balls between them
+    let color = |player: u8| if player == game.player { WHITE } else { GRAY };
+    let score = at(offset + 16.0, 32.0);
+    canvas.draw_text(&format!("{:03}", game.player_score(0)), score.x, score.y, cell, color(0));
+    let text = format!("{:03}", game.player_score(1));
+    let score = at(offset + game.size.x - 16.0, 32.0);
+    canvas.draw_text(&text, score.x - text_width(&text, cell), score.y, cell, color(1));
+    let text = game.balls_rem.to_string();
+    let balls = at(offset + game.size.x / 2.0, 32.0);
+    canvas.draw_text(&text, balls.x - text_width(&text, cell) / 2.0, balls.y, cell, WHITE);
+}
+
+/// Width in canvas pixels of `text` drawn with `Canvas::draw_text`.
+fn text_width(text: &str, cell: f32) -> f32 {
+    (text.chars().count() * (GLYPH_WIDTH + 1) - 1) as f32 * cell
+}
+
+/// A power-up capsule with its letter, its top left at `pos` in canvas pixels.
+fn draw_capsule(canvas: &mut Canvas, kind: PowerUp, pos: Vec2, scale: Vec2) {
+    let size = CAPSULE_SIZE * scale;
+    canvas.fill_rect(pos.x, pos.y, size.x, size.y, kind.color());
+    let cell = CAPSULE_CELL * scale.y;
+    let letter = pos + (size - vec2(GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32) * cell) / 2.0;
+    canvas.draw_text(kind.letter(), letter.x, letter.y, cell, WHITE);
+}
+
+/// The pixel range `[start, end)` covered by `len` units from `pos`, clipped to `0..limit`.
+fn span(pos: f32, len: f32, limit: usize) -> Option<(usize, usize)> {
+    if len <= 0.0 {
+        return None;
+    }
+    let (mut start, mut end) = ((pos - 0.5).ceil(), (pos + len - 0.5).ceil());
+    if end <= start {
+        start = (pos + len / 2.0).floor();
+        end = start + 1.0;
+    }
+    let start = start.clamp(0.0, limit as f32) as usize;
+    let end = end.clamp(0.0, limit as f32) as usize;
+    (start < end).then_some((start, end))
+}
+
+fn rgba(color: Color) -> [u8; 4] {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [channel(color.r), channel(color.g), channel(color.b), channel(color.a)]
+}
+
+/// The rows of a character's glyph from the top, the leftmost block in the highest bit.
+fn glyph(ch: char) -> Option<[u8; GLYPH_HEIGHT]> {
+    Some(match ch {
+        '0' => [0b01110, 0b10001, 0b10011, 0b10101, 0b11001, 0b10001, 0b01110],
+        '1' => [0b00100, 0b01100, 0b00100, 0b00100, 0b00100, 0b00100, 0b01110],
+        '2' => [0b01110, 0b10001, 0b00001, 0b00010, 0b00100, 0b01000, 0b11111],
+        '3' => [0b11111, 0b00010, 0b00100, 0b00010, 0b00001, 0b10001, 0b01110],
+        '4' => [0b00010, 0b00110, 0b01010, 0b10010, 0b11111, 0b00010, 0b00010],
+        '5' => [0b11111, 0b10000, 0b11110, 0b00001, 0b00001, 0b10001, 0b01110],
+        '6' => [0b00110, 0b01000, 0b10000, 0b11110, 0b10001, 0b10001, 0b01110],
+        '7' => [0b11111, 0b00001, 0b00010, 0b00100, 0b01000, 0b01000, 0b01000],
+        '8' => [0b01110, 0b10001, 0b10001, 0b01110, 0b10001, 0b10001, 0b01110],
+        '9' => [0b01110, 0b10001, 0b10001, 0b01111, 0b00001, 0b00010, 0b01100],
+        'C' => [0b01110, 0b10001, 0b10000, 0b10000, 0b10000, 0b10001, 0b01110],
+        'D' => [0b11100, 0b10010, 0b10001, 0b10001, 0b10001, 0b10010, 0b11100],
+        'E' => [0b11111, 0b10000, 0b10000, 0b11110, 0b10000, 0b10000, 0b11111],
+        'L' => [0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b10000, 0b11111],
+        'P' => [0b11110, 0b10001, 0b10001, 0b11110, 0b10000, 0b10000, 0b10000],
+        'S' => [0b01111, 0b10000, 0b10000, 0b01110, 0b00001, 0b00001, 0b11110],
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::ai::{Autoplay, Skill, Strategy};
+    use crate::breakout::{GAME_WIDTH, TickInput, Tuning};
+    use crate::level::Level;
+    use crate::rules::Ruleset;
+
+    /// The colors of a canvas as one character a pixel, `#` for `color` and `.` for anything
+    /// else.
+    fn mask(canvas: &Canvas, color: Color) -> Vec<String> {
+        let rgba = rgba(color);
+        canvas.pixels.chunks_exact(4 * canvas.width)
+            .map(|row| row.chunks_exact(4).map(|pixel| if pixel == rgba { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    fn game(players: u8) -> Breakout {
+        let mut game = Breakout::new(vec2(GAME_WIDTH, GAME_HEIGHT), Ruleset::Arcade, Level::default(), 11)
+            .with_tuning(Tuning { players, ..Tuning::default() });
+        game.start();
+        game
+    }
+
+    /// Compares a canvas with the PNG of the same name in `screenshots/`. Run with
+    /// `UPDATE_SCREENSHOTS=1` to write the canvas there instead, after checking it looks right.
+    fn assert_screenshot(name: &str, canvas: &Canvas) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("screenshots").join(format!("{}.png", name));
+        if std::env::var_os("UPDATE_SCREENSHOTS").is_some() {
+            canvas.write_png(&path).unwrap();
+            return;
+        }
+        let decoder = png::Decoder::new(File::open(&path).unwrap_or_else(|err| panic!("{}: {}", path.display(), err)));
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        let golden = Canvas { width: reader.info().width as usize, height: reader.info().height as usize, pixels };
+        if *canvas != golden {
+            let actual = std::env::temp_dir().join(format!("{}.png", name));
+            canvas.write_png(&actual).unwrap();
+            panic!("{} differs from {}, see {}", name, path.display(), actual.display());
+        }
+    }
+
+    #[test]
+    fn fill_rect_fills_the_pixels_centred_inside() {
+        let mut canvas = Canvas::new(6, 3);
+        canvas.fill_rect(0.6, 0.4, 2.0, 1.2, WHITE);
+        canvas.fill_rect(4.2, 1.2, 0.3, 0.3, RED);
+        // clipped to the canvas
+        canvas.fill_rect(4.5, -1.0, 10.0, 2.0, WHITE);
+        assert_eq!(mask(&canvas, WHITE), [".##.##", ".##...", "......"]);
+        // too small to cover a centre, but still drawn
+        assert_eq!(mask(&canvas, RED), ["......", "....#.", "......"]);
+        canvas.fill_rect(-5.0, 10.0, 3.0, 3.0, RED);
+        canvas.fill_rect(1.0, 1.0, 0.0, 2.0, RED);
+        assert_eq!(mask(&canvas, RED), ["......", "....#.", "......"]);
+    }
+
+    #[test]
+    fn stroke_rect_draws_inside_the_edges() {
+        let mut canvas = Canvas::new(5, 4);
+        canvas.stroke_rect(0.0, 0.0, 5.0, 4.0, 2.0, WHITE);
+        assert_eq!(mask(&canvas, WHITE), ["#####", "#...#", "#...#", "#####"]);
+    }
+
+    #[test]
+    fn draw_text_uses_the_block_font() {
+        let mut canvas = Canvas::new(11, 7);
+        canvas.draw_text("1 ?", 0.0, 0.0, 1.0, WHITE);
+        canvas.draw_text("7", 6.0, 0.0, 1.0, WHITE);
+        assert_eq!(mask(&canvas, WHITE), [
+            "..#...#####",
+            ".##.......#",
+            "..#......#.",
+            "..#.....#..",
+            "..#....#...",
+            "..#....#...",
+            ".###...#...",
+        ]);
+        assert_eq!(text_width("17", 1.0), 11.0);
+    }
+
+    #[test]
+    fn draws_a_new_game() {
+        let mut canvas = Canvas::new(320, 240);
+        draw_screen(&mut canvas, &game(1));
+        assert_screenshot("new-game", &canvas);
+    }
+
+    #[test]
+    fn draws_a_game_in_play() {
+        let mut game = game(1);
+        let mut autoplay = Autoplay::new(Skill::Perfect, Strategy::Tunnel, 11);
+        for _ in 0..6000 {
+            let input = autoplay.input(&game);
+            game.step(input);
+        }
+        let mut canvas = Canvas::new(640, 480);
+        draw_screen(&mut canvas, &game);
+        assert_screenshot("in-play", &canvas);
+    }
+
+    #[test]
+    fn draws_both_scores_for_two_players() {
+        let mut game = game(2);
+        // lose the first ball, so player 2 is up and player 1 has a score to show
+        game.score = 42;
+        while game.player == 0 {
+            game.step(TickInput { paddle_x: 0.0, fire: false });
+        }
+        let mut canvas = Canvas::new(640, 480);
+        draw_screen(&mut canvas, &game);
+        assert_screenshot("two-players", &canvas);
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use gif::{DisposalMethod, Encoder, Frame, Repeat};
+
+use breakout::breakout::TICK_RATE;
+use breakout::raster::{Canvas, SCREEN_SIZE, draw_screen};
+use breakout::replay::{Playback, Replay};
+
+use crate::config::parse_flag;
+
+pub const USAGE: &str = "breakout render <replay> -o <out.gif or directory> [--fps <n>] [--scale <x>]";
+
+const DEFAULT_FPS: u32 = 30;
+// GIF frame delays are in hundredths of a second, and viewers slow down anything under two
+const MAX_FPS: u32 = 50;
+const DEFAULT_SCALE: f32 = 0.5;
+const MIN_SCALE: f32 = 0.1;
+const MAX_SCALE: f32 = 2.0;
+// seconds the last frame stays up before the GIF starts over
+const HOLD_TIME: u16 = 2;
+
+/// What `breakout render` was asked to do.
+pub struct RenderOptions {
+    pub replay: PathBuf,
+    /// An animated GIF if it ends in `.gif`, otherwise a directory for numbered PNGs.
+    pub output: PathBuf,
+    pub fps: u32,
+    /// Size of the frames as a multiple of the 1280 by 960 screen.
+    pub scale: f32,
+}
+
+impl RenderOptions {
+    pub fn from_args(args: &[String]) -> Result<RenderOptions, String> {
+        let mut replay = None;
+        let mut output = None;
+        let mut fps = DEFAULT_FPS;
+        let mut scale = DEFAULT_SCALE;
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+            match arg.as_str() {
+                "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+                "--fps" => fps = parse_flag(arg, value()?, 1..=MAX_FPS)?,
+                "--scale" => scale = parse_flag(arg, value()?, MIN_SCALE..=MAX_SCALE)?,
+                flag if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+                _ if replay.is_none() => replay = Some(PathBuf::from(arg)),
+                _ => return Err(format!("unexpected argument `{}`", arg)),
+            }
+        }
+        Ok(RenderOptions {
+            replay: replay.ok_or("no replay given")?,
+            output: output.ok_or("no output given, use -o")?,
+            fps,
+            scale,
+        })
+    }
+
+    fn is_gif(&self) -> bool {
+        self.output.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("gif"))
+    }
+}
+
+/// Where the frames go.
+enum Sink {
+    /// The encoder and the last frame written to it.
+    Gif(Encoder<BufWriter<File>>, Option<Canvas>),
+    Frames(PathBuf),
+}
+
+impl Sink {
+    /// Adds a frame that stays up for `delay` hundredths of a second.
+    fn write(&mut self, canvas: &Canvas, index: usize, delay: u16) -> Result<(), String> {
+        match self {
+            Sink::Gif(encoder, last) => {
+                // only the part that changed is stored, drawn over the frame before
+                let (left, top, width, height) = match last {
+                    Some(last) => changed(last, canvas),
+                    None => (0, 0, canvas.width, canvas.height),
+                };
+                let mut frame = gif_frame(&crop(canvas, left, top, width, height));
+                frame.left = left as u16;
+                frame.top = top as u16;
+                frame.delay = delay;
+                frame.dispose = DisposalMethod::Keep;
+                *last = Some(canvas.clone());
+                encoder.write_frame(&frame).map_err(|err| err.to_string())
+            }
+            Sink::Frames(dir) => {
+                let path = dir.join(format!("{:05}.png", index));
+                canvas.write_png(&path).map_err(|err| format!("{}: {}", path.display(), err))
+            }
+        }
+    }
+}
+
+/// Plays a replay through the software renderer and writes out its frames. Returns how many
+/// were written.
+pub fn render(options: &RenderOptions) -> Result<usize, String> {
+    let replay = Replay::load(&options.replay).map_err(|err| format!("{}: {}", options.replay.display(), err))?;
+    let width = (SCREEN_SIZE.x * options.scale).round() as u16;
+    let height = (SCREEN_SIZE.y * options.scale).round() as u16;
+    let mut sink = open(&options.output, options.is_gif(), width, height)?;
+
+    let mut playback = Playback::new(replay);
+    let mut canvas = Canvas::new(width as usize, height as usize);
+    // delays are whole hundredths of a second, so they are taken from the running time to
+    // keep the total right
+    let centis = |frame: usize| frame as u64 * 100 / options.fps as u64;
+    let mut tick = 0;
+    let mut index = 0;
+    loop {
+        draw_screen(&mut canvas, &playback.game);
+        let finished = playback.finished();
+        let mut delay = (centis(index + 1) - centis(index)) as u16;
+        if finished {
+            delay += HOLD_TIME * 100;
+        }
+        sink.write(&canvas, index, delay)?;
+        index += 1;
+        if finished {
+            return Ok(index);
+        }
+
+        let next = index as u64 * TICK_RATE as u64 / options.fps as u64;
+        while tick < next && playback.step() {
+            tick += 1;
+        }
+    }
+}
+
+fn open(output: &Path, gif: bool, width: u16, height: u16) -> Result<Sink, String> {
+    let error = |err: &dyn std::fmt::Display| format!("{}: {}", output.display(), err);
+    if !gif {
+        fs::create_dir_all(output).map_err(|err| error(&err))?;
+        return Ok(Sink::Frames(output.to_owned()));
+    }
+    let file = File::create(output).map_err(|err| error(&err))?;
+    let mut encoder = Encoder::new(BufWriter::new(file), width, height, &[]).map_err(|err| error(&err))?;
+    encoder.set_repeat(Repeat::Infinite).map_err(|err| error(&err))?;
+    Ok(Sink::Gif(encoder, None))
+}
+
+/// The canvas as a GIF frame. The game only uses a handful of colors, so they are given a
+/// palette of their own exactly, and only a frame with more than 256 is quantized.
+fn gif_frame(canvas: &Canvas) -> Frame<'static> {
+    let (width, height) = (canvas.width as u16, canvas.height as u16);
+    let mut palette = Vec::new();
+    let mut colors: HashMap<[u8; 3], u8> = HashMap::new();
+    let mut indices = Vec::with_capacity(canvas.width * canvas.height);
+    for pixel in canvas.pixels.chunks_exact(4) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match colors.get(&color) {
+            Some(&index) => index,
+            None if colors.len() < 256 => {
+                let index = colors.len() as u8;
+                colors.insert(color, index);
+                palette.extend_from_slice(&color);
+                index
+            }
+            None => return Frame::from_rgba_speed(width, height, &mut canvas.pixels.clone(), 10),
+        };
+        indices.push(index);
+    }
+    Frame::from_palette_pixels(width, height, indices, palette, None)
+}
+
+/// The smallest rectangle, as left, top, width and height, holding every pixel that differs
+/// between two canvases of the same size. Unchanged frames still get one pixel, as a GIF frame
+/// can't be empty.
+fn changed(before: &Canvas, after: &Canvas) -> (usize, usize, usize, usize) {
+    let (mut left, mut top, mut right, mut bottom) = (after.width, after.height, 0, 0);
+    let pixels = before.pixels.chunks_exact(4).zip(after.pixels.chunks_exact(4));
+    for (i, _) in pixels.enumerate().filter(|(_, (a, b))| a != b) {
+        let (x, y) = (i % after.width, i / after.width);
+        left = left.min(x);
+        right = right.max(x + 1);
+        top = top.min(y);
+        bottom = bottom.max(y + 1);
+    }
+    if right == 0 {
+        return (0, 0, 1, 1);
+    }
+    (left, top, right - left, bottom - top)
+}
+
+fn crop(canvas: &Canvas, left: usize, top: usize, width: usize, height: usize) -> Canvas {
+    let mut cropped = Canvas::new(width, height);
+    for row in 0..height {
+        let start = ((top + row) * canvas.width + left) * 4;
+        cropped.pixels[row * width * 4..(row + 1) * width * 4].copy_from_slice(&canvas.pixels[start..start + width * 4]);
+    }
+    cropped
+}
+
+#[cfg(test)]
+mod tests {
+    use macroquad::math::vec2;
+
+    use breakout::breakout::{Breakout, GAME_HEIGHT, GAME_WIDTH, TickInput};
+    use breakout::level::Level;
+    use breakout::rules::Ruleset;
+
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// A directory of its own for one test, holding a replay of `seconds` of play.
+    fn scratch(name: &str, seconds: u32) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("breakout-render-test-{}-{}", std::process::id(), name));
+        let mut game = Breakout::new(vec2(GAME_WIDTH, GAME_HEIGHT), Ruleset::default(), Level::default(), 1);
+        game.start();
+        for _ in 0..seconds * TICK_RATE {
+            game.step(TickInput { paddle_x: game.balls[0].pos.x - 20.0, fire: false });
+        }
+        Replay::from_game(&game).save(dir.join("game.replay")).unwrap();
+        dir
+    }
+
+    fn options(dir: &Path, output: &str, fps: u32, scale: f32) -> RenderOptions {
+        RenderOptions { replay: dir.join("game.replay"), output: dir.join(output), fps, scale }
+    }
+
+    #[test]
+    fn reads_the_options() {
+        let options = RenderOptions::from_args(&args(&["game.replay", "-o", "out.gif", "--fps", "12", "--scale", "0.25"])).unwrap();
+        assert_eq!((options.replay, options.output, options.fps, options.scale), ("game.replay".into(), "out.gif".into(), 12, 0.25));
+        let options = RenderOptions::from_args(&args(&["game.replay", "--output", "frames"])).unwrap();
+        assert_eq!((options.fps, options.scale, options.is_gif()), (DEFAULT_FPS, DEFAULT_SCALE, false));
+    }
+
+    #[test]
+    fn rejects_options_out_of_range() {
+        for (flags, message) in [
+            (["--fps", "0"], "--fps: expected a number from 1 to 50, got `0`"),
+            (["--fps", "60"], "--fps: expected a number from 1 to 50, got `60`"),
+            (["--scale", "0.05"], "--scale: expected a number from 0.1 to 2, got `0.05`"),
+            (["--scale", "3"], "--scale: expected a number from 0.1 to 2, got `3`"),
+        ] {
+            let mut all = args(&["game.replay", "-o", "out.gif"]);
+            all.extend(args(&flags));
+            assert_eq!(RenderOptions::from_args(&all).err().as_deref(), Some(message));
+        }
+        assert_eq!(RenderOptions::from_args(&args(&["game.replay"])).err().as_deref(), Some("no output given, use -o"));
+        assert_eq!(RenderOptions::from_args(&args(&["-o", "out.gif"])).err().as_deref(), Some("no replay given"));
+        assert_eq!(RenderOptions::from_args(&args(&["a", "b"])).err().as_deref(), Some("unexpected argument `b`"));
+    }
+
+    #[test]
+    fn writes_a_frame_for_every_step_of_the_frame_rate() {
+        let dir = scratch("frames", 2);
+        // the first frame, then one every tenth of a second
+        assert_eq!(render(&options(&dir, "frames", 10, 0.1)).unwrap(), 21);
+        let mut names: Vec<_> = fs::read_dir(dir.join("frames")).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names.len(), 21);
+        assert_eq!((names[0].to_str(), names[20].to_str()), (Some("00000.png"), Some("00020.png")));
+
+        let reader = png::Decoder::new(File::open(dir.join("frames/00020.png")).unwrap()).read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (128, 96));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn writes_a_gif_that_lasts_as_long_as_the_game() {
+        let dir = scratch("gif", 1);
+        assert_eq!(render(&options(&dir, "game.gif", 30, 0.25)).unwrap(), 31);
+
+        let mut decoder = gif::DecodeOptions::new().read_info(File::open(dir.join("game.gif")).unwrap()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (320, 240));
+        let mut frames = 0;
+        let mut centis = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            frames += 1;
+            centis += frame.delay as u32;
+        }
+        assert_eq!(frames, 31);
+        // a second of play, the last frame's own thirtieth and the hold at the end
+        assert_eq!(centis, 103 + HOLD_TIME as u32 * 100);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,515 @@
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use macroquad::math::{Vec2, vec2};
+
+use crate::breakout::{Breakout, Deflection, MAX_PLAYERS, Outcome, TickInput, Tuning};
+use crate::choice::Choice;
+use crate::level::{Level, LevelError};
+use crate::paths::data_dir;
+use crate::rules::Ruleset;
+
+const MAGIC: &[u8; 4] = b"BKRP";
+const VERSION: u16 = 4;
+const FIRE: u8 = 1;
+
+/// A recorded game: the settings it was started with and the input of every tick, which is
+/// enough to play it again exactly.
+///
+/// The file is little-endian binary: a header with the magic, version, seed, ruleset, playfield
+/// size, the level as text, the tuning and the claimed outcome, followed by the inputs
+/// run-length encoded as `(varint count, f32 paddle x, u8 flags)`. Version 1 files have no
+/// outcome, versions before 3 no tuning and versions before 4 no player count.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub ruleset: Ruleset,
+    pub size: Vec2,
+    pub level: Level,
+    pub tuning: Tuning,
+    /// How the recorded game ended, if it did.
+    pub outcome: Option<Outcome>,
+    pub inputs: Vec<TickInput>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(std::io::Error),
+    Format(String),
+    Level(LevelError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "could not read replay: {}", err),
+            ReplayError::Format(message) => write!(f, "invalid replay: {}", message),
+            ReplayError::Level(err) => write!(f, "invalid level in replay: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+impl From<std::io::Error> for ReplayError {
+    fn from(err: std::io::Error) -> Self {
+        ReplayError::Io(err)
+    }
+}
+
+impl Replay {
+    /// The replay of a game so far, from when it was started.
+    pub fn from_game(game: &Breakout) -> Replay {
+        Replay {
+            seed: game.seed,
+            ruleset: game.ruleset,
+            size: game.size,
+            level: game.level.clone(),
+            tuning: game.tuning,
+            outcome: game.outcome,
+            inputs: game.inputs.clone(),
+        }
+    }
+
+    /// Where finished games are saved.
+    pub fn default_dir() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("replays"))
+    }
+
+    /// A game in the state the recording started from.
+    pub fn new_game(&self) -> Breakout {
+        let mut game = Breakout::new(self.size, self.ruleset, self.level.clone(), self.seed).with_tuning(self.tuning);
+        game.start();
+        game
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Replay, ReplayError> {
+        Replay::decode(&fs::read(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.encode())
+    }
+
+    /// Saves to `<name>.replay` in `dir`, or `<name>-2.replay` and so on if that is taken, so
+    /// that games ending at the same moment don't overwrite each other. Returns the path used.
+    pub fn save_new(&self, dir: impl AsRef<Path>, name: &str) -> std::io::Result<PathBuf> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let bytes = self.encode();
+        for n in 1.. {
+            let path = match n {
+                1 => dir.join(format!("{}.replay", name)),
+                n => dir.join(format!("{}-{}.replay", name, n)),
+            };
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => return file.write_all(&bytes).map(|()| path),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        unreachable!("ran out of replay names")
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.extend_from_slice(&self.seed.to_le_bytes());
+        write_str(&mut out, self.ruleset.id());
+        out.extend_from_slice(&self.size.x.to_le_bytes());
+        out.extend_from_slice(&self.size.y.to_le_bytes());
+        write_str(&mut out, &self.level.to_text());
+        out.push(self.tuning.lives);
+        out.extend_from_slice(&self.tuning.speed.to_le_bytes());
+        out.extend_from_slice(&self.tuning.deflection.min_angle.to_le_bytes());
+        out.extend_from_slice(&self.tuning.deflection.max_angle.to_le_bytes());
+        out.push(self.tuning.players);
+        match self.outcome {
+            Some(outcome) => {
+                out.push(1);
+                out.extend_from_slice(&outcome.score.to_le_bytes());
+                out.push(outcome.balls_rem);
+                out.extend_from_slice(&outcome.hash.to_le_bytes());
+            }
+            None => out.push(0),
+        }
+
+        write_varint(&mut out, self.inputs.len() as u64);
+        let mut inputs = self.inputs.iter().peekable();
+        while let Some(&input) = inputs.next() {
+            let mut count = 1;
+            while inputs.next_if_eq(&&input).is_some() {
+                count += 1;
+            }
+            write_varint(&mut out, count);
+            out.extend_from_slice(&input.paddle_x.to_le_bytes());
+            out.push(if input.fire { FIRE } else { 0 });
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Replay, ReplayError> {
+        let mut reader = Reader::new(bytes);
+        if reader.take(4)? != MAGIC {
+            return Err(ReplayError::Format("not a replay file".into()));
+        }
+        let version = u16::from_le_bytes(reader.array()?);
+        if version == 0 || version > VERSION {
+            return Err(ReplayError::Format(format!("unsupported version {}", version)));
+        }
+        let seed = u64::from_le_bytes(reader.array()?);
+        let ruleset = reader.str()?;
+        let ruleset = Ruleset::from_id(ruleset).ok_or_else(|| ReplayError::Format(format!("unknown ruleset `{}`", ruleset)))?;
+        let size = vec2(f32::from_le_bytes(reader.array()?), f32::from_le_bytes(reader.array()?));
+        if !Breakout::playable(size) {
+            return Err(ReplayError::Format(format!("impossible playfield size {}", size)));
+        }
+        let level = Level::parse(reader.str()?).map_err(ReplayError::Level)?;
+        let tuning = match version {
+            1 | 2 => Tuning::default(),
+            _ => Tuning {
+                lives: reader.array::<1>()?[0],
+                speed: f32::from_le_bytes(reader.array()?),
+                deflection: Deflection {
+                    min_angle: f32::from_le_bytes(reader.array()?),
+                    max_angle: f32::from_le_bytes(reader.array()?),
+                },
+                players: if version == 3 { 1 } else { reader.array::<1>()?[0] },
+            },
+        };
+        if tuning.lives == 0 || !(tuning.speed > 0.0 && tuning.speed.is_finite()) {
+            return Err(ReplayError::Format("impossible lives or ball speed".into()));
+        }
+        if !tuning.deflection.valid() {
+            return Err(ReplayError::Format("impossible paddle angles".into()));
+        }
+        if !(1..=MAX_PLAYERS).contains(&tuning.players) {
+            return Err(ReplayError::Format(format!("unsupported number of players {}", tuning.players)));
+        }
+        let outcome = match version {
+            1 => None,
+            _ => match reader.array::<1>()?[0] {
+                0 => None,
+                _ => Some(Outcome {
+                    score: u16::from_le_bytes(reader.array()?),
+                    balls_rem: reader.array::<1>()?[0],
+                    hash: u64::from_le_bytes(reader.array()?),
+                }),
+            },
+        };
+
+        let total = reader.varint()? as usize;
+        // don't trust the count for the allocation, a corrupt file could claim anything
+        let mut inputs = Vec::with_capacity(total.min(bytes.len() * 8));
+        while inputs.len() < total {
+            let count = reader.varint()? as usize;
+            let paddle_x = f32::from_le_bytes(reader.array()?);
+            let fire = reader.array::<1>()?[0] & FIRE != 0;
+            if count == 0 || count > total - inputs.len() {
+                return Err(ReplayError::Format("input runs don't add up to the tick count".into()));
+            }
+            inputs.extend(std::iter::repeat_n(TickInput { paddle_x, fire }, count));
+        }
+        if !reader.done() {
+            return Err(ReplayError::Format("trailing data after the inputs".into()));
+        }
+
+        Ok(Replay { seed, ruleset, size, level, tuning, outcome, inputs })
+    }
+}
+
+/// Plays a replay's inputs back into a game at the normal tick rate.
+pub struct Playback {
+    pub replay: Replay,
+    pub game: Breakout,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            game: replay.new_game(),
+            replay,
+            next: 0,
+        }
+    }
+
+    /// Plays as many ticks as fit in `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        for _ in 0..self.game.advance(dt) {
+            self.step();
+        }
+    }
+
+    /// Plays the next recorded tick. Returns false once the recording has run out.
+    pub fn step(&mut self) -> bool {
+        let Some(&input) = self.replay.inputs.get(self.next) else {
+            return false;
+        };
+        self.game.step(input);
+        self.next += 1;
+        true
+    }
+
+    /// Plays the rest of the recording as fast as possible.
+    pub fn run_to_end(&mut self) {
+        while self.step() {}
+    }
+
+    pub fn finished(&self) -> bool {
+        self.next >= self.replay.inputs.len()
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, text: &str) {
+    write_varint(out, text.len() as u64);
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// LEB128: seven bits at a time, low bits first, with the top bit set on all but the last byte.
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads the little-endian fields of a file in order, failing with `ReplayError::Format` if it
+/// ends early.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    /// Whether every byte has been read.
+    pub(crate) fn done(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], ReplayError> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| ReplayError::Format("file ends early".into()))?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn array<const N: usize>(&mut self) -> Result<[u8; N], ReplayError> {
+        Ok(self.take(N)?.try_into().expect("took N bytes"))
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<u64, ReplayError> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.array::<1>()?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(ReplayError::Format("number too long".into()))
+    }
+
+    fn str(&mut self) -> Result<&'a str, ReplayError> {
+        let len = self.varint()? as usize;
+        std::str::from_utf8(self.take(len)?).map_err(|_| ReplayError::Format("text is not UTF-8".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{Autoplay, Skill, Strategy};
+    use crate::breakout::{GAME_HEIGHT, GAME_WIDTH};
+
+    // long enough to lose a ball or two and break some bricks, short enough for a debug build
+    const TICKS: usize = 20_000;
+
+    /// Plays a game with the computer at the paddle until it ends or `TICKS` have gone by.
+    fn play(seed: u64, tuning: Tuning) -> Breakout {
+        let mut game = Breakout::new(vec2(GAME_WIDTH, GAME_HEIGHT), Ruleset::Arcade, Level::default(), seed).with_tuning(tuning);
+        game.start();
+        let mut autoplay = Autoplay::new(Skill::Easy, Strategy::Scatter, seed);
+        while game.outcome.is_none() && game.inputs.len() < TICKS {
+            let input = autoplay.input(&game);
+            game.step(input);
+        }
+        game
+    }
+
+    fn format_error(bytes: &[u8]) -> String {
+        match Replay::decode(bytes) {
+            Err(ReplayError::Format(message)) => message,
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("replay decoded"),
+        }
+    }
+
+    /// Where the playfield size starts in an encoded replay.
+    fn size_offset(replay: &Replay) -> usize {
+        let mut header = Vec::new();
+        write_str(&mut header, replay.ruleset.id());
+        4 + 2 + 8 + header.len()
+    }
+
+    #[test]
+    fn encoding_round_trips() {
+        let deflection = Deflection { min_angle: 5.0, max_angle: 70.0 };
+        let replay = Replay::from_game(&play(7, Tuning { lives: 2, speed: 1.5, deflection, players: 1 }));
+        let bytes = replay.encode();
+        let decoded = Replay::decode(&bytes).unwrap();
+        assert_eq!(decoded.seed, 7);
+        assert_eq!(decoded.ruleset, replay.ruleset);
+        assert_eq!(decoded.size, replay.size);
+        assert_eq!(decoded.level.to_text(), replay.level.to_text());
+        assert_eq!(decoded.tuning, replay.tuning);
+        assert_eq!(decoded.outcome, replay.outcome);
+        assert_eq!(decoded.inputs, replay.inputs);
+        assert_eq!(decoded.encode(), bytes);
+    }
+
+    #[test]
+    fn playback_reproduces_the_game() {
+        for (seed, players) in [(1, 1), (2, 1), (3, 2)] {
+            let game = play(seed, Tuning { players, ..Tuning::default() });
+            let replay = Replay::decode(&Replay::from_game(&game).encode()).unwrap();
+            let mut playback = Playback::new(replay);
+            playback.run_to_end();
+            assert!(playback.finished());
+            assert!(game.outcome.is_some(), "seed {} didn't finish", seed);
+            assert_eq!(playback.game.state_hash(), game.state_hash(), "seed {}", seed);
+            assert_eq!(playback.game.outcome, game.outcome);
+            assert_eq!(playback.game.score, game.score);
+        }
+    }
+
+    #[test]
+    fn the_seed_changes_the_game() {
+        let replay = Replay::from_game(&play(5, Tuning::default()));
+        let mut other = replay.clone();
+        other.seed = 6;
+        let mut a = Playback::new(replay);
+        let mut b = Playback::new(other);
+        a.run_to_end();
+        b.run_to_end();
+        assert_ne!(a.game.state_hash(), b.game.state_hash());
+    }
+
+    #[test]
+    fn reads_version_3_files() {
+        let mut replay = Replay::from_game(&play(4, Tuning::default()));
+        replay.inputs.truncate(100);
+        replay.outcome = None;
+        let mut bytes = replay.encode();
+        bytes[4..6].copy_from_slice(&3u16.to_le_bytes());
+        // version 3 has no player count after the lives, speed and paddle angles
+        let mut level = Vec::new();
+        write_str(&mut level, &replay.level.to_text());
+        bytes.remove(size_offset(&replay) + 8 + level.len() + 13);
+
+        let decoded = Replay::decode(&bytes).unwrap();
+        assert_eq!(decoded.tuning, Tuning::default());
+        assert_eq!(decoded.inputs, replay.inputs);
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let mut replay = Replay::from_game(&play(9, Tuning::default()));
+        replay.inputs.truncate(50);
+        let bytes = replay.encode();
+
+        assert_eq!(format_error(b"GIF89a"), "not a replay file");
+        let mut future = bytes.clone();
+        future[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(format_error(&future), format!("unsupported version {}", VERSION + 1));
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(format_error(&trailing), "trailing data after the inputs");
+        for len in 0..bytes.len() {
+            assert!(Replay::decode(&bytes[..len]).is_err(), "{} bytes decoded", len);
+        }
+    }
+
+    #[test]
+    fn rejects_impossible_settings() {
+        let replay = Replay { inputs: Vec::new(), outcome: None, ..Replay::from_game(&play(3, Tuning::default())) };
+        let offset = size_offset(&replay);
+        for size in [vec2(1.0, 1.0), vec2(f32::NAN, GAME_HEIGHT), vec2(GAME_WIDTH, f32::INFINITY)] {
+            let mut bytes = replay.encode();
+            bytes[offset..offset + 4].copy_from_slice(&size.x.to_le_bytes());
+            bytes[offset + 4..offset + 8].copy_from_slice(&size.y.to_le_bytes());
+            assert!(format_error(&bytes).starts_with("impossible playfield size"), "{}", size);
+        }
+
+        for (tuning, message) in [
+            (Tuning { lives: 0, ..Tuning::default() }, "impossible lives or ball speed"),
+            (Tuning { speed: f32::NAN, ..Tuning::default() }, "impossible lives or ball speed"),
+            (Tuning { deflection: Deflection { min_angle: 50.0, max_angle: 40.0 }, ..Tuning::default() }, "impossible paddle angles"),
+            (Tuning { deflection: Deflection { min_angle: 0.0, max_angle: 90.0 }, ..Tuning::default() }, "impossible paddle angles"),
+            (Tuning { players: 0, ..Tuning::default() }, "unsupported number of players 0"),
+            (Tuning { players: MAX_PLAYERS + 1, ..Tuning::default() }, "unsupported number of players 3"),
+        ] {
+            let bytes = Replay { tuning, ..replay.clone() }.encode();
+            assert_eq!(format_error(&bytes), message);
+        }
+    }
+
+    #[test]
+    fn rejects_runs_that_overshoot_the_tick_count() {
+        let replay = Replay {
+            inputs: vec![TickInput { paddle_x: 0.0, fire: false }; 3],
+            outcome: None,
+            ..Replay::from_game(&play(3, Tuning::default()))
+        };
+        let mut bytes = replay.encode();
+        // the single run of 3 is the last 6 bytes
+        let runs = bytes.len() - 6;
+        for count in [0, 4, u64::MAX] {
+            bytes.truncate(runs);
+            write_varint(&mut bytes, count);
+            bytes.extend_from_slice(&0.0f32.to_le_bytes());
+            bytes.push(0);
+            assert_eq!(format_error(&bytes), "input runs don't add up to the tick count", "count {}", count);
+        }
+    }
+
+    #[test]
+    fn save_new_keeps_earlier_replays() {
+        let dir = std::env::temp_dir().join(format!("breakout-replay-test-{}", std::process::id()));
+        let mut first = Replay::from_game(&play(1, Tuning::default()));
+        first.inputs.truncate(10);
+        let mut second = first.clone();
+        second.seed = 2;
+        let paths = [first.save_new(&dir, "game").unwrap(), second.save_new(&dir, "game").unwrap()];
+        let seeds = paths.each_ref().map(|path| Replay::load(path).unwrap().seed);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(paths.map(|path| path.file_name().unwrap().to_owned()), ["game.replay", "game-2.replay"]);
+        assert_eq!(seeds, [1, 2]);
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut bytes = Vec::new();
+            write_varint(&mut bytes, value);
+            let mut reader = Reader { bytes: &bytes, pos: 0 };
+            assert_eq!(reader.varint().unwrap(), value);
+            assert_eq!(reader.pos, bytes.len());
+        }
+        let mut reader = Reader { bytes: &[0xff; 11], pos: 0 };
+        assert!(reader.varint().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use crate::breakout::{BASE_SPEED, PADDLE_SIZE};
+use crate::choice::Choice;
+
+// brick hits after which the ball speeds up
+const SPEED_UP_HITS: [u32; 2] = [4, 12];
+// rows whose first contact speeds the ball up, counted from the top of the wall so they are the
+// red and orange bands of the arcade wall however many rows a level has
+const RED_ROWS: std::ops::Range<u8> = 0..2;
+const ORANGE_ROWS: std::ops::Range<u8> = 2..4;
+// speed gained per step, as a fraction of the starting speed
+const SPEED_STEP: f32 = 0.15;
+// points for clearing one full wall: 14 columns of two rows each at 1, 3, 5 and 7 points
+const WALL_SCORE: u16 = 448;
+
+/// Which set of rules a game is played under.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Ruleset {
+    /// A single wall of bricks. Clearing it wins the game.
+    #[default]
+    SingleWall,
+    /// The original two-screen game. A second wall follows the first and the score is capped at
+    /// 896 points.
+    Arcade,
+}
+
+impl Choice for Ruleset {
+    const CHOICES: &'static [(Ruleset, &'static str, &'static str)] = &[
+        (Ruleset::SingleWall, "single-wall", "Single Wall"),
+        (Ruleset::Arcade, "arcade", "Arcade"),
+    ];
+    const KIND: &'static str = "ruleset";
+}
+
+impl Ruleset {
+    /// Number of walls that have to be cleared to win.
+    pub fn walls(&self) -> u8 {
+        match self {
+            Ruleset::SingleWall => 1,
+            Ruleset::Arcade => 2,
+        }
+    }
+
+    pub fn max_score(&self) -> Option<u16> {
+        match self {
+            Ruleset::SingleWall => None,
+            Ruleset::Arcade => Some(WALL_SCORE * self.walls() as u16),
+        }
+    }
+}
+
+/// The arcade difficulty rules: the ball speeds up as bricks are hit and the paddle halves once
+/// the ball breaks through to the top wall.
+#[derive(Clone, Default)]
+pub struct Rules {
+    pub hits: u32,
+    pub hit_orange: bool,
+    pub hit_red: bool,
+    pub hit_ceiling: bool,
+}
+
+impl Rules {
+    /// Ball speed in pixels per millisecond.
+    pub fn ball_speed(&self) -> f32 {
+        let steps = SPEED_UP_HITS.iter().filter(|&&hits| self.hits >= hits).count()
+            + self.hit_orange as usize
+            + self.hit_red as usize;
+        // the starting speed is BASE_SPEED on each axis
+        BASE_SPEED * std::f32::consts::SQRT_2 * (1.0 + SPEED_STEP * steps as f32)
+    }
+
+    pub fn paddle_width(&self) -> f32 {
+        if self.hit_ceiling {
+            PADDLE_SIZE.x / 2.0
+        } else {
+            PADDLE_SIZE.x
+        }
+    }
+
+    /// Records a hit on a brick `row` rows down from the top of the wall. Returns true if the
+    /// ball speed changed.
+    pub fn brick_hit(&mut self, row: u8) -> bool {
+        let speed = self.ball_speed();
+        self.hits += 1;
+        self.hit_orange |= ORANGE_ROWS.contains(&row);
+        self.hit_red |= RED_ROWS.contains(&row);
+        self.ball_speed() != speed
+    }
+
+    /// Records the ball reaching the top wall, which halves the paddle.
+    pub fn ceiling_hit(&mut self) {
+        self.hit_ceiling = true;
+    }
+
+    /// Starts counting again for a new ball. The paddle stays shrunk until the wall is cleared.
+    pub fn new_serve(&mut self) {
+        *self = Self {
+            hit_ceiling: self.hit_ceiling,
+            ..Self::default()
+        };
+    }
+}
@@ -0,0 +1,439 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use macroquad::color::Color;
+use macroquad::math::{Vec2, vec2};
+
+use crate::breakout::{Ball, Breakout, Brick, BrickKind, Fnv, GameState, Turn};
+use crate::paths::data_dir;
+use crate::powerup::{Capsule, PowerUp};
+use crate::replay::{Reader, Replay, ReplayError, write_varint};
+use crate::rng::Rng;
+use crate::rules::Rules;
+
+const FILE_NAME: &str = "saved-game";
+const MAGIC: &[u8; 4] = b"BKSV";
+const VERSION: u16 = 1;
+const RANKED: u8 = 1;
+const CHECKSUM_LEN: usize = 8;
+const BRICK_KINDS: [BrickKind; 3] = [BrickKind::Normal, BrickKind::Steel, BrickKind::Explosive];
+
+/// A paused game put aside to be continued later, exactly as it was left.
+#[derive(Clone)]
+pub struct SavedGame {
+    /// The game, paused.
+    pub game: Breakout,
+    /// Whether the game's score can go on the high-score table.
+    pub ranked: bool,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Format(String),
+    Replay(ReplayError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not read saved game: {}", err),
+            SaveError::Format(message) => write!(f, "invalid saved game: {}", message),
+            SaveError::Replay(err) => write!(f, "saved game: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(err: std::io::Error) -> Self {
+        SaveError::Io(err)
+    }
+}
+
+impl From<ReplayError> for SaveError {
+    /// The state is read with the replay's reader, whose format errors are the save's own.
+    fn from(err: ReplayError) -> Self {
+        match err {
+            ReplayError::Format(message) => SaveError::Format(message),
+            err => SaveError::Replay(err),
+        }
+    }
+}
+
+impl SavedGame {
+    /// Saves a paused game. It has to have been started, so that its replay covers all of it.
+    pub fn from_game(game: &Breakout, ranked: bool) -> SavedGame {
+        let mut game = game.clone();
+        game.game_state = GameState::Paused;
+        game.sounds.clear();
+        SavedGame { game, ranked }
+    }
+
+    /// The saved-game file in the user's data directory.
+    pub fn default_path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join(FILE_NAME))
+    }
+
+    /// Reads the saved game from `path`, if there is one.
+    pub fn load(path: impl AsRef<Path>) -> Result<Option<SavedGame>, SaveError> {
+        match fs::read(path) {
+            Ok(bytes) => SavedGame::decode(&bytes).map(Some),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.encode())
+    }
+
+    /// Removes the saved-game file at `path`. It is fine for there to be none.
+    pub fn remove(path: impl AsRef<Path>) -> std::io::Result<()> {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Little-endian binary: the magic, version and flags, the replay so far (which holds the
+    /// game's settings and keeps a continued game's replay complete), the state of every part of
+    /// the game, and an FNV-1a checksum of all of that.
+    pub fn encode(&self) -> Vec<u8> {
+        let game = &self.game;
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        out.push(if self.ranked { RANKED } else { 0 });
+        let replay = Replay::from_game(game).encode();
+        write_varint(&mut out, replay.len() as u64);
+        out.extend_from_slice(&replay);
+
+        out.extend_from_slice(&game.ticks.to_le_bytes());
+        out.extend_from_slice(&game.rng.state().to_le_bytes());
+        out.push(game.player);
+        write_turn(&mut out, &game.bricks, &game.rules, game.score, game.balls_rem, game.game_count);
+        match &game.waiting {
+            Some(turn) => {
+                out.push(1);
+                write_turn(&mut out, &turn.bricks, &turn.rules, turn.score, turn.balls_rem, turn.game_count);
+            }
+            None => out.push(0),
+        }
+        write_vec(&mut out, game.paddle_pos);
+        out.extend_from_slice(&game.paddle_target.to_le_bytes());
+        write_varint(&mut out, game.balls.len() as u64);
+        for ball in &game.balls {
+            write_vec(&mut out, ball.pos);
+            write_vec(&mut out, ball.vel);
+            write_vec(&mut out, ball.prev_pos);
+            out.push(ball.hit_paddle as u8);
+            match ball.caught {
+                Some(offset) => {
+                    out.push(1);
+                    out.extend_from_slice(&offset.to_le_bytes());
+                }
+                None => out.push(0),
+            }
+        }
+        write_varint(&mut out, game.capsules.len() as u64);
+        for capsule in &game.capsules {
+            write_vec(&mut out, capsule.pos);
+            out.push(power_up_index(capsule.kind));
+        }
+        write_varint(&mut out, game.lasers.len() as u64);
+        for &laser in &game.lasers {
+            write_vec(&mut out, laser);
+        }
+        out.extend_from_slice(&game.laser_cooldown.to_le_bytes());
+        write_varint(&mut out, game.effects.active.len() as u64);
+        for &(kind, ticks) in &game.effects.active {
+            out.push(power_up_index(kind));
+            out.extend_from_slice(&ticks.to_le_bytes());
+        }
+
+        let mut checksum = Fnv::new();
+        checksum.write(&out);
+        out.extend_from_slice(&checksum.0.to_le_bytes());
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<SavedGame, SaveError> {
+        if bytes.len() < MAGIC.len() + 2 + CHECKSUM_LEN || &bytes[..4] != MAGIC {
+            return Err(SaveError::Format("not a saved game".into()));
+        }
+        let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+        if version == 0 || version > VERSION {
+            return Err(SaveError::Format(format!("unsupported version {}", version)));
+        }
+        let (body, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+        let mut expected = Fnv::new();
+        expected.write(body);
+        if expected.0.to_le_bytes() != checksum {
+            return Err(SaveError::Format("checksum does not match, the file is damaged".into()));
+        }
+
+        let mut reader = Reader::new(&body[6..]);
+        let ranked = reader.array::<1>()?[0] & RANKED != 0;
+        let len = reader.varint()? as usize;
+        let replay = Replay::decode(reader.take(len)?).map_err(SaveError::Replay)?;
+        let mut game = replay.new_game();
+        game.inputs = replay.inputs;
+        game.game_state = GameState::Paused;
+        game.sounds.clear();
+
+        game.ticks = u64::from_le_bytes(reader.array()?);
+        game.rng = Rng::new(u64::from_le_bytes(reader.array()?));
+        game.player = reader.array::<1>()?[0];
+        let turn = read_turn(&mut reader)?;
+        (game.bricks, game.rules, game.score, game.balls_rem, game.game_count) =
+            (turn.bricks, turn.rules, turn.score, turn.balls_rem, turn.game_count);
+        let waiting = match reader.array::<1>()?[0] {
+            0 => None,
+            1 => Some(read_turn(&mut reader)?),
+            _ => return Err(SaveError::Format("bad player flag".into())),
+        };
+        if waiting.is_some() != game.waiting.is_some() || game.player >= game.tuning.players {
+            return Err(SaveError::Format("the players don't match the game's settings".into()));
+        }
+        game.waiting = waiting;
+        game.paddle_pos = read_vec(&mut reader)?;
+        game.paddle_target = f32::from_le_bytes(reader.array()?);
+
+        game.balls = Vec::new();
+        for _ in 0..reader.varint()? {
+            let mut ball = Ball::new(read_vec(&mut reader)?, read_vec(&mut reader)?);
+            ball.prev_pos = read_vec(&mut reader)?;
+            ball.hit_paddle = read_flag(&mut reader)?;
+            if read_flag(&mut reader)? {
+                ball.caught = Some(f32::from_le_bytes(reader.array()?));
+            }
+            game.balls.push(ball);
+        }
+        if game.balls.is_empty() {
+            return Err(SaveError::Format("no ball in play".into()));
+        }
+        game.capsules = Vec::new();
+        for _ in 0..reader.varint()? {
+            let pos = read_vec(&mut reader)?;
+            game.capsules.push(Capsule { pos, kind: read_power_up(&mut reader)? });
+        }
+        game.lasers = Vec::new();
+        for _ in 0..reader.varint()? {
+            game.lasers.push(read_vec(&mut reader)?);
+        }
+        game.laser_cooldown = u32::from_le_bytes(reader.array()?);
+        game.effects.clear();
+        for _ in 0..reader.varint()? {
+            let kind = read_power_up(&mut reader)?;
+            let ticks = u32::from_le_bytes(reader.array()?);
+            if kind.duration().is_none() || ticks == 0 {
+                return Err(SaveError::Format("impossible power-up effect".into()));
+            }
+            game.effects.activate(kind, ticks);
+        }
+        if !reader.done() {
+            return Err(SaveError::Format("trailing data after the game".into()));
+        }
+        Ok(SavedGame { game, ranked })
+    }
+}
+
+fn write_vec(out: &mut Vec<u8>, v: Vec2) {
+    out.extend_from_slice(&v.x.to_le_bytes());
+    out.extend_from_slice(&v.y.to_le_bytes());
+}
+
+fn read_vec(reader: &mut Reader) -> Result<Vec2, SaveError> {
+    Ok(vec2(f32::from_le_bytes(reader.array()?), f32::from_le_bytes(reader.array()?)))
+}
+
+fn read_flag(reader: &mut Reader) -> Result<bool, SaveError> {
+    match reader.array::<1>()?[0] {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(SaveError::Format("bad flag".into())),
+    }
+}
+
+fn power_up_index(kind: PowerUp) -> u8 {
+    PowerUp::ALL.iter().position(|&other| other == kind).expect("every power-up is in ALL") as u8
+}
+
+fn read_power_up(reader: &mut Reader) -> Result<PowerUp, SaveError> {
+    let index = reader.array::<1>()?[0] as usize;
+    PowerUp::ALL.get(index).copied().ok_or_else(|| SaveError::Format("unknown power-up".into()))
+}
+
+/// One player's wall, rules, score, balls and walls cleared.
+fn write_turn(out: &mut Vec<u8>, bricks: &[Brick], rules: &Rules, score: u16, balls_rem: u8, game_count: u8) {
+    write_varint(out, bricks.len() as u64);
+    for brick in bricks {
+        write_vec(out, brick.pos);
+        let kind = BRICK_KINDS.iter().position(|&kind| kind == brick.kind).expect("every kind is listed");
+        out.extend_from_slice(&[brick.row, kind as u8, brick.hits, brick.max_hits]);
+        for channel in [brick.color.r, brick.color.g, brick.color.b, brick.color.a] {
+            out.extend_from_slice(&channel.to_le_bytes());
+        }
+        out.extend_from_slice(&brick.points.to_le_bytes());
+    }
+    out.extend_from_slice(&rules.hits.to_le_bytes());
+    out.extend_from_slice(&[rules.hit_orange as u8, rules.hit_red as u8, rules.hit_ceiling as u8]);
+    out.extend_from_slice(&score.to_le_bytes());
+    out.extend_from_slice(&[balls_rem, game_count]);
+}
+
+fn read_turn(reader: &mut Reader) -> Result<Turn, SaveError> {
+    let mut bricks = Vec::new();
+    for _ in 0..reader.varint()? {
+        let pos = read_vec(reader)?;
+        let [row, kind, hits, max_hits] = reader.array()?;
+        let kind = *BRICK_KINDS.get(kind as usize).ok_or_else(|| SaveError::Format("unknown brick kind".into()))?;
+        if hits == 0 || hits > max_hits {
+            return Err(SaveError::Format("impossible brick hits".into()));
+        }
+        let mut channels = [0.0; 4];
+        for channel in &mut channels {
+            *channel = f32::from_le_bytes(reader.array()?);
+        }
+        let [r, g, b, a] = channels;
+        let points = u16::from_le_bytes(reader.array()?);
+        bricks.push(Brick { pos, row, kind, color: Color::new(r, g, b, a), hits, max_hits, points });
+    }
+    let rules = Rules {
+        hits: u32::from_le_bytes(reader.array()?),
+        hit_orange: read_flag(reader)?,
+        hit_red: read_flag(reader)?,
+        hit_ceiling: read_flag(reader)?,
+    };
+    let score = u16::from_le_bytes(reader.array()?);
+    let [balls_rem, game_count] = reader.array()?;
+    Ok(Turn { bricks, rules, score, balls_rem, game_count })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{Autoplay, Skill, Strategy};
+    use crate::breakout::{GAME_HEIGHT, GAME_WIDTH, Tuning};
+    use crate::level::Level;
+    use crate::rules::Ruleset;
+
+    /// A game for two some way in, with a power-up falling and one running, and the computer
+    /// that has been playing it.
+    fn game_in_progress() -> (Breakout, Autoplay) {
+        let mut game = Breakout::new(vec2(GAME_WIDTH, GAME_HEIGHT), Ruleset::Arcade, Level::default(), 5)
+            .with_tuning(Tuning { players: 2, ..Tuning::default() });
+        game.start();
+        let mut autoplay = Autoplay::new(Skill::Normal, Strategy::Tunnel, 5);
+        for _ in 0..6000 {
+            let input = autoplay.input(&game);
+            game.step(input);
+        }
+        assert!(game.game_state == GameState::Playing);
+        game.capsules.push(Capsule { pos: vec2(100.0, 400.0), kind: PowerUp::Laser });
+        game.effects.activate(PowerUp::Slow, 500);
+        (game, autoplay)
+    }
+
+    /// `bytes` with the checksum worked out again, as if the damage was deliberate.
+    fn resealed(mut bytes: Vec<u8>) -> Vec<u8> {
+        bytes.truncate(bytes.len().saturating_sub(CHECKSUM_LEN));
+        let mut checksum = Fnv::new();
+        checksum.write(&bytes);
+        bytes.extend_from_slice(&checksum.0.to_le_bytes());
+        bytes
+    }
+
+    fn format_error(bytes: &[u8]) -> String {
+        match SavedGame::decode(bytes) {
+            Err(SaveError::Format(message)) => message,
+            Err(err) => panic!("unexpected error: {}", err),
+            Ok(_) => panic!("saved game decoded"),
+        }
+    }
+
+    #[test]
+    fn a_loaded_game_carries_on_exactly() {
+        let (mut game, mut autoplay) = game_in_progress();
+        let saved = SavedGame::from_game(&game, true);
+        let bytes = saved.encode();
+        let loaded = SavedGame::decode(&bytes).unwrap();
+        assert!(loaded.ranked);
+        assert_eq!(loaded.encode(), bytes);
+
+        let mut continued = loaded.game;
+        assert!(continued.game_state == GameState::Paused);
+        assert_eq!(continued.inputs, game.inputs);
+        game.game_state = GameState::Paused;
+        assert_eq!(continued.state_hash(), game.state_hash());
+
+        game.game_state = GameState::Playing;
+        continued.game_state = GameState::Playing;
+        for _ in 0..6000 {
+            let input = autoplay.input(&game);
+            game.step(input);
+            continued.step(input);
+        }
+        assert_eq!(continued.state_hash(), game.state_hash());
+        assert_eq!((continued.player_score(0), continued.player_score(1)), (game.player_score(0), game.player_score(1)));
+    }
+
+    #[test]
+    fn saves_and_loads() {
+        let dir = std::env::temp_dir().join(format!("breakout-save-test-{}", std::process::id()));
+        let path = dir.join(FILE_NAME);
+        assert!(SavedGame::load(&path).unwrap().is_none());
+        let (game, _) = game_in_progress();
+        SavedGame::from_game(&game, false).save(&path).unwrap();
+        let loaded = SavedGame::load(&path).unwrap().unwrap();
+        assert!(!loaded.ranked);
+        assert_eq!(loaded.game.score, game.score);
+        SavedGame::remove(&path).unwrap();
+        SavedGame::remove(&path).unwrap();
+        assert!(SavedGame::load(&path).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_damaged_files() {
+        let (game, _) = game_in_progress();
+        let bytes = SavedGame::from_game(&game, true).encode();
+
+        // the ranked flag is covered by the checksum like everything else
+        let mut unranked = bytes.clone();
+        unranked[6] = 0;
+        assert_eq!(format_error(&unranked), "checksum does not match, the file is damaged");
+        let mut flipped = bytes.clone();
+        flipped[bytes.len() / 2] ^= 0x10;
+        assert_eq!(format_error(&flipped), "checksum does not match, the file is damaged");
+        for len in [0, 5, 20, bytes.len() / 2, bytes.len() - 1] {
+            assert!(SavedGame::decode(&bytes[..len]).is_err(), "decoded {} bytes", len);
+        }
+
+        assert_eq!(format_error(b"BKRP\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00"), "not a saved game");
+        let mut future = bytes.clone();
+        future[4] = 9;
+        assert_eq!(format_error(&future), "unsupported version 9");
+    }
+
+    #[test]
+    fn rejects_truncated_files_even_with_a_good_checksum() {
+        let (game, _) = game_in_progress();
+        let bytes = SavedGame::from_game(&game, true).encode();
+        assert_eq!(format_error(&resealed(bytes[..bytes.len() - 20].to_vec())), "file ends early");
+        let mut longer = bytes.clone();
+        longer.insert(bytes.len() - CHECKSUM_LEN, 0);
+        assert_eq!(format_error(&resealed(longer)), "trailing data after the game");
+        // the last thing written is the running effect's ticks
+        let mut no_ticks = bytes.clone();
+        no_ticks[bytes.len() - CHECKSUM_LEN - 4..bytes.len() - CHECKSUM_LEN].fill(0);
+        assert_eq!(format_error(&resealed(no_ticks)), "impossible power-up effect");
+    }
+}
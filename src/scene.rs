@@ -0,0 +1,68 @@
+/// A screen of the game. Scenes stack up, so that closing one returns to whatever it was
+/// opened from: options opened from the pause menu go back to it, and it to the game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scene {
+    Title,
+    ModeSelect,
+    Options,
+    ControlsOptions,
+    AudioOptions,
+    VideoOptions,
+    HighScores,
+    Credits,
+    Game,
+    Paused,
+    NameEntry,
+    /// The high-score table after a game.
+    Results,
+}
+
+/// A scene and the menu item selected in it.
+pub struct Layer {
+    pub scene: Scene,
+    pub selected: usize,
+}
+
+/// The open scenes, with the one in front last. There is always at least one.
+pub struct SceneStack {
+    layers: Vec<Layer>,
+}
+
+impl SceneStack {
+    pub fn new(scene: Scene) -> Self {
+        Self { layers: vec![Layer { scene, selected: 0 }] }
+    }
+
+    pub fn top(&self) -> Scene {
+        self.top_layer().scene
+    }
+
+    pub fn top_layer(&self) -> &Layer {
+        self.layers.last().expect("the scene stack is never empty")
+    }
+
+    pub fn top_layer_mut(&mut self) -> &mut Layer {
+        self.layers.last_mut().expect("the scene stack is never empty")
+    }
+
+    pub fn push(&mut self, scene: Scene) {
+        self.layers.push(Layer { scene, selected: 0 });
+    }
+
+    /// Closes the front scene, unless it is the last one.
+    pub fn pop(&mut self) {
+        if self.layers.len() > 1 {
+            self.layers.pop();
+        }
+    }
+
+    /// Swaps the front scene for another.
+    pub fn replace(&mut self, scene: Scene) {
+        *self.top_layer_mut() = Layer { scene, selected: 0 };
+    }
+
+    /// Closes everything and opens `scene`.
+    pub fn reset(&mut self, scene: Scene) {
+        *self = Self::new(scene);
+    }
+}
@@ -0,0 +1,86 @@
+use macroquad::camera::{Camera2D, set_camera, set_default_camera};
+use macroquad::color::{BLACK, WHITE};
+use macroquad::input::mouse_position;
+use macroquad::math::{Vec2, vec2};
+use macroquad::texture::{DrawTextureParams, FilterMode, RenderTarget, draw_texture_ex, render_target};
+use macroquad::window::{clear_background, screen_height, screen_width};
+
+use breakout::raster::SCREEN_SIZE;
+
+// the same virtual screen the software renderer draws
+pub const VIRTUAL_WIDTH: f32 = SCREEN_SIZE.x;
+pub const VIRTUAL_HEIGHT: f32 = SCREEN_SIZE.y;
+
+/// A fixed-size virtual screen that everything is drawn to, which is then scaled to fit the
+/// window with black bars on the sides that don't match its shape.
+pub struct Screen {
+    target: RenderTarget,
+    camera: Camera2D,
+    integer_scaling: bool,
+}
+
+impl Screen {
+    pub fn new(integer_scaling: bool) -> Self {
+        let target = render_target(VIRTUAL_WIDTH as u32, VIRTUAL_HEIGHT as u32);
+        // unlike from_display_rect the y axis isn't flipped, since render targets come out
+        // upside down
+        let camera = Camera2D {
+            target: vec2(VIRTUAL_WIDTH / 2.0, VIRTUAL_HEIGHT / 2.0),
+            zoom: vec2(2.0 / VIRTUAL_WIDTH, 2.0 / VIRTUAL_HEIGHT),
+            render_target: Some(target.clone()),
+            ..Default::default()
+        };
+        let mut screen = Self {
+            target,
+            camera,
+            integer_scaling,
+        };
+        screen.set_integer_scaling(integer_scaling);
+        screen
+    }
+
+    /// Only scale by whole numbers, so every virtual pixel is the same size. The picture is
+    /// smaller but sharp, unless the window is too small to fit it at 1x.
+    pub fn set_integer_scaling(&mut self, on: bool) {
+        self.integer_scaling = on;
+        self.target.texture.set_filter(if on { FilterMode::Nearest } else { FilterMode::Linear });
+    }
+
+    /// Window pixels per virtual pixel.
+    pub fn scale(&self) -> f32 {
+        let scale = f32::min(screen_width() / VIRTUAL_WIDTH, screen_height() / VIRTUAL_HEIGHT);
+        if self.integer_scaling && scale >= 1.0 {
+            scale.floor()
+        } else {
+            scale
+        }
+    }
+
+    /// Where the top-left corner of the virtual screen is in the window.
+    fn origin(&self) -> Vec2 {
+        let size = vec2(VIRTUAL_WIDTH, VIRTUAL_HEIGHT) * self.scale();
+        (vec2(screen_width(), screen_height()) - size) / 2.0
+    }
+
+    /// The mouse position in virtual pixels.
+    pub fn mouse_position(&self) -> Vec2 {
+        (Vec2::from(mouse_position()) - self.origin()) / self.scale()
+    }
+
+    /// Sends drawing to the virtual screen until `end`.
+    pub fn begin(&self) {
+        set_camera(&self.camera);
+    }
+
+    /// Draws the virtual screen to the window.
+    pub fn end(&self) {
+        set_default_camera();
+        clear_background(BLACK);
+        let origin = self.origin();
+        let params = DrawTextureParams {
+            dest_size: Some(vec2(VIRTUAL_WIDTH, VIRTUAL_HEIGHT) * self.scale()),
+            ..Default::default()
+        };
+        draw_texture_ex(&self.target.texture, origin.x, origin.y, WHITE, params);
+    }
+}
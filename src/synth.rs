@@ -0,0 +1,234 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// The shape of an oscillator's output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    /// A pulse wave, high for `duty` of each cycle. 0.5 is a plain square.
+    Square { duty: f32 },
+    Triangle,
+    /// Pseudo-random noise from a 15-bit shift register clocked at the voice's frequency, like the
+    /// noise channel of old sound chips. It is the same on every run.
+    Noise,
+}
+
+/// How loudness changes over a note, with times in seconds and `sustain` a level from 0.0 to
+/// 1.0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Envelope {
+    pub attack: f32,
+    pub decay: f32,
+    pub sustain: f32,
+    pub release: f32,
+}
+
+impl Envelope {
+    /// A click of an attack and a fade out over `release`.
+    pub const fn pluck(release: f32) -> Self {
+        Self { attack: 0.002, decay: 0.0, sustain: 1.0, release }
+    }
+
+    /// The level `t` seconds into a note held for `hold` seconds.
+    pub fn level(&self, t: f32, hold: f32) -> f32 {
+        if t < hold {
+            return self.held_level(t);
+        }
+        // release from wherever the note got to, even if it was cut short during the attack
+        let released = (t - hold) / self.release.max(f32::EPSILON);
+        self.held_level(hold) * (1.0 - released).max(0.0)
+    }
+
+    fn held_level(&self, t: f32) -> f32 {
+        if t < self.attack {
+            t / self.attack
+        } else if t < self.attack + self.decay {
+            1.0 - (1.0 - self.sustain) * (t - self.attack) / self.decay
+        } else {
+            self.sustain
+        }
+    }
+}
+
+/// A single note: an oscillator sliding from `freq` to `freq_end`, shaped by an envelope.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Voice {
+    pub waveform: Waveform,
+    /// Hz at the start of the note.
+    pub freq: f32,
+    /// Hz at the end of the note, the pitch slides linearly in between.
+    pub freq_end: f32,
+    pub volume: f32,
+    pub envelope: Envelope,
+    /// Seconds until the release starts.
+    pub hold: f32,
+    /// Seconds after the start of the sound at which this note begins.
+    pub start: f32,
+}
+
+impl Voice {
+    pub fn new(waveform: Waveform, freq: f32, hold: f32, envelope: Envelope) -> Self {
+        Self { waveform, freq, freq_end: freq, volume: 0.5, envelope, hold, start: 0.0 }
+    }
+
+    pub fn slide_to(self, freq_end: f32) -> Self {
+        Self { freq_end, ..self }
+    }
+
+    pub fn volume(self, volume: f32) -> Self {
+        Self { volume, ..self }
+    }
+
+    pub fn at(self, start: f32) -> Self {
+        Self { start, ..self }
+    }
+
+    /// Seconds from the start of the note until it has faded out.
+    pub fn duration(&self) -> f32 {
+        self.hold + self.envelope.release
+    }
+
+    /// Adds the note into `out`, which holds samples at `sample_rate` from the start of the sound.
+    pub fn render_into(&self, out: &mut Vec<f32>, sample_rate: u32) {
+        let rate = sample_rate as f32;
+        let first = (self.start * rate) as usize;
+        let len = (self.duration() * rate) as usize;
+        if out.len() < first + len {
+            out.resize(first + len, 0.0);
+        }
+
+        let mut phase = 0.0f32;
+        let mut lfsr: u16 = 1;
+        for i in 0..len {
+            let t = i as f32 / rate;
+            let progress = t / self.duration();
+            let freq = self.freq + (self.freq_end - self.freq) * progress;
+            let sample = match self.waveform {
+                Waveform::Square { duty } => if phase < duty { 1.0 } else { -1.0 },
+                Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                Waveform::Noise => if lfsr & 1 == 0 { 1.0 } else { -1.0 },
+            };
+            out[first + i] += sample * self.volume * self.envelope.level(t, self.hold);
+
+            phase += freq / rate;
+            while phase >= 1.0 {
+                phase -= 1.0;
+                let feedback = (lfsr ^ (lfsr >> 1)) & 1;
+                lfsr = (lfsr >> 1) | (feedback << 14);
+            }
+        }
+    }
+}
+
+/// Mixes notes into one buffer of mono samples, clipped to `-1.0..=1.0`.
+pub fn render(voices: &[Voice], sample_rate: u32) -> Vec<f32> {
+    let mut out = Vec::new();
+    for voice in voices {
+        voice.render_into(&mut out, sample_rate);
+    }
+    for sample in &mut out {
+        *sample = sample.clamp(-1.0, 1.0);
+    }
+    out
+}
+
+/// Encodes mono samples as a 16-bit PCM `.wav` file.
+pub fn wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut out = Vec::with_capacity(44 + data_len as usize);
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(36 + data_len).to_le_bytes());
+    out.extend_from_slice(b"WAVEfmt ");
+    out.extend_from_slice(&16u32.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    out.extend_from_slice(&1u16.to_le_bytes()); // mono
+    out.extend_from_slice(&sample_rate.to_le_bytes());
+    out.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    out.extend_from_slice(&2u16.to_le_bytes());
+    out.extend_from_slice(&16u16.to_le_bytes());
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        out.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    out
+}
+
+pub fn write_wav(path: impl AsRef<Path>, samples: &[f32], sample_rate: u32) -> io::Result<()> {
+    fs::write(path, wav(samples, sample_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // full volume from the first sample to the last, so the samples are the bare waveform
+    const FLAT: Envelope = Envelope { attack: 0.0, decay: 0.0, sustain: 1.0, release: 0.0 };
+
+    #[test]
+    fn oscillators_match_their_golden_samples() {
+        let square = Voice::new(Waveform::Square { duty: 0.5 }, 2.0, 1.0, FLAT).volume(1.0);
+        assert_eq!(render(&[square], 8), [1.0, 1.0, -1.0, -1.0, 1.0, 1.0, -1.0, -1.0]);
+        let pulse = Voice::new(Waveform::Square { duty: 0.25 }, 1.0, 1.0, FLAT).volume(0.5);
+        assert_eq!(render(&[pulse], 8), [0.5, 0.5, -0.5, -0.5, -0.5, -0.5, -0.5, -0.5]);
+        let triangle = Voice::new(Waveform::Triangle, 1.0, 2.0, FLAT).volume(1.0);
+        assert_eq!(render(&[triangle], 4), [-1.0, 0.0, 1.0, 0.0, -1.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn noise_repeats_like_a_15_bit_shift_register() {
+        // clocked once a sample, the register runs through all 32767 non-zero states
+        const PERIOD: u32 = (1 << 15) - 1;
+        let noise = Voice::new(Waveform::Noise, PERIOD as f32, 2.0, FLAT).volume(1.0);
+        let samples = render(&[noise], PERIOD);
+        let (first, second) = samples.split_at(PERIOD as usize);
+        assert_eq!(first, second);
+        assert_eq!(first.iter().filter(|&&sample| sample < 0.0).count(), 1 << 14);
+        assert_eq!(&first[..4], [-1.0, 1.0, 1.0, 1.0]);
+        // and a second render is the same as the first
+        assert_eq!(render(&[noise], PERIOD), samples);
+    }
+
+    #[test]
+    fn slides_change_the_pitch() {
+        let slide = Voice::new(Waveform::Square { duty: 0.5 }, 1.0, 1.0, FLAT).slide_to(3.0).volume(1.0);
+        let samples = render(&[slide], 16);
+        let flips = samples.windows(2).filter(|pair| pair[0] != pair[1]).count();
+        // two whole cycles in the second, where a steady 1 Hz only flips once halfway
+        assert_eq!(flips, 3);
+    }
+
+    #[test]
+    fn envelope_levels() {
+        let envelope = Envelope { attack: 0.1, decay: 0.2, sustain: 0.5, release: 0.4 };
+        let expected = [(0.0, 0.0), (0.05, 0.5), (0.2, 0.75), (0.5, 0.5), (1.2, 0.25), (1.4, 0.0), (5.0, 0.0)];
+        for (t, level) in expected {
+            assert!((envelope.level(t, 1.0) - level).abs() < 1e-6, "level at {} is {}", t, envelope.level(t, 1.0));
+        }
+        // released halfway through the attack, it fades from where it got to
+        assert!((envelope.level(0.25, 0.05) - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn voices_mix_at_their_start_times_and_clip() {
+        let note = Voice::new(Waveform::Square { duty: 0.5 }, 1.0, 0.5, FLAT).volume(0.75);
+        let samples = render(&[note, note.at(0.25)], 4);
+        assert_eq!(samples, [0.75, 1.0, 0.75]);
+        assert_eq!(note.at(0.25).duration(), 0.5);
+    }
+
+    #[test]
+    fn wav_files_hold_16_bit_samples() {
+        let bytes = wav(&[0.0, 0.5, -1.0, 2.0], 22050);
+        assert_eq!(bytes.len(), 44 + 8);
+        assert_eq!(&bytes[..4], b"RIFF");
+        assert_eq!(&bytes[4..8], &44u32.to_le_bytes());
+        assert_eq!(&bytes[8..16], b"WAVEfmt ");
+        assert_eq!(&bytes[24..28], &22050u32.to_le_bytes());
+        assert_eq!(&bytes[28..32], &44100u32.to_le_bytes());
+        assert_eq!(&bytes[36..40], b"data");
+        assert_eq!(&bytes[40..44], &8u32.to_le_bytes());
+        let samples: Vec<i16> = bytes[44..].chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]])).collect();
+        assert_eq!(samples, [0, 16383, -32767, 32767]);
+    }
+}